@@ -1,13 +1,23 @@
 use std::fs;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use anyhow::Result;
 use eframe::CreationContext;
 use eframe::egui::Visuals;
 use serde::{Deserialize, Serialize};
-use tracing::{error, warn};
 use common::get_config_path;
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct ClientConfig {
-    // todo: key
+    /// has to match the key in the server's config.toml
+    #[serde(default)]
+    pub key: Option<String>,
+    /// websocket url of the server, found through the file the server writes on startup if not set
+    #[serde(default)]
+    pub server_url: Option<String>,
+    /// where tokio-console can connect, 127.0.0.1:6670 if not set
+    #[serde(default)]
+    pub console_address: Option<SocketAddr>,
 
     #[serde(default)]
     pub theme: Theme,
@@ -44,14 +54,11 @@ impl Theme {
     }
 }
 
-pub fn load_config() -> ClientConfig {
+/// A missing client.toml gives the default config
+pub fn load_config() -> Result<ClientConfig> {
     match fs::read_to_string(get_config_path().join("client.toml")) {
-        Ok(file) => {
-            toml::from_str(&file).map_err(|e| error!("Failed to parse config: {e}")).unwrap()
-        }
-        Err(err) => {
-            warn!("Unable to read config: {err}");
-            ClientConfig::default()
-        }
+        Ok(file) => Ok(toml::from_str(&file)?),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(ClientConfig::default()),
+        Err(e) => Err(e.into()),
     }
 }
\ No newline at end of file
@@ -1,29 +1,36 @@
+use std::fs;
 use std::ops::Deref;
 use std::time::Duration;
 use tokio::{select};
 use tokio::sync::mpsc::{UnboundedReceiver};
-use tokio::time::sleep;
+use tokio::time::{interval, sleep};
 use tracing::{error, info, instrument};
 use websockets::{Frame, WebSocket, WebSocketError};
 use anyhow::Result;
 use crate::SState;
-use common::ws_common::{ClientToServer, ServerToClient};
+use common::get_discovery_path;
+use crate::audio_manager::SoundEffects;
+use common::history::PeriodEnd;
+use common::timer::PeriodType;
+use common::ws_common::{Capability, ClientKind, ClientToServer, ServerToClient, TimerEvent, HEARTBEAT_INTERVAL, PROTOCOL_VERSION};
+use egui_toast::ToastKind;
 use ServerToClient::*;
 
-const URL: &str = "ws://127.0.0.1:63086/ws";
+const DEFAULT_URL: &str = "ws://127.0.0.1:63086/ws";
 
 /// Handles reconnections and message processing
 #[instrument(name = "client ws", skip_all)]
 pub async fn ws_loop(state: SState, mut rx: UnboundedReceiver<ClientToServer>) {
     loop {
-        let e = match WebSocket::connect(URL).await {
+        let url = server_url(&state);
+        let e = match WebSocket::connect(&url).await {
             Ok(ws) => {
                 info!("Connection established");
                 select_loop(&state, ws, &mut rx).await.unwrap_err()
             },
             Err(err) => err,
         };
-        error!("Stopped with error \"{0}\". Retrying in 3 seconds ", e.to_string());
+        error!("Stopped with error \"{0}\" ({url}). Retrying in 3 seconds ", e.to_string());
         if let Ok(mut state) = state.lock(){
             state.ws_connected = false;
             if let Some(ref ctx) = state.egui_context {ctx.request_repaint()}
@@ -34,15 +41,38 @@ pub async fn ws_loop(state: SState, mut rx: UnboundedReceiver<ClientToServer>) {
     }
 }
 
+/// The configured url, otherwise the address the server wrote when it started, read again on every reconnect
+fn server_url(state: &SState) -> String {
+    if let Some(url) = state.lock().ok().and_then(|state| state.config.server_url.clone()) {
+        return url;
+    }
+    match fs::read_to_string(get_discovery_path()) {
+        Ok(address) => format!("ws://{}/ws", address.trim()),
+        Err(_) => DEFAULT_URL.to_string(),
+    }
+}
+
 async fn select_loop(state: &SState, mut ws: WebSocket, rx: &mut UnboundedReceiver<ClientToServer>) -> Result<(),WebSocketError> {
     // empty the receiver
     while rx.try_recv().is_ok() { }
 
-    if let Ok(mut state) = state.lock() {
-        state.ws_connected = true;
-        if let Some(ref ctx) = state.egui_context {ctx.request_repaint()}
+    // the server won't process anything else until the handshake is done
+    let key = state.lock().ok().and_then(|state| state.config.key.clone());
+    let mut handshake = vec![ClientToServer::Hello {
+        protocol_version: PROTOCOL_VERSION,
+        client_kind: ClientKind::App,
+        client_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        capabilities: Capability::SUPPORTED.to_vec(),
+    }];
+    handshake.extend(key.map(|key| ClientToServer::Authenticate { key }));
+    for msg in handshake {
+        match serde_json::to_string(&msg) {
+            Ok(text) => ws.send(Frame::text(text)).await?,
+            Err(e) => error!("Failed to serialize message: {e}"),
+        }
     }
 
+    let mut heartbeat = interval(HEARTBEAT_INTERVAL);
     let mut incomplete_payload: Option<String> = None;
     loop {
         select! {
@@ -70,6 +100,11 @@ async fn select_loop(state: &SState, mut ws: WebSocket, rx: &mut UnboundedReceiv
                     Ok(text) => ws.send(Frame::text(text)).await?,
                     Err(e) => error!("Failed to serialize message: {e}"),
                 },
+            _ = heartbeat.tick() =>
+                match serde_json::to_string(&ClientToServer::Heartbeat) {
+                    Ok(text) => ws.send(Frame::text(text)).await?,
+                    Err(e) => error!("Failed to serialize message: {e}"),
+                },
         }
     }
 }
@@ -83,17 +118,59 @@ fn handle_msg(state: &SState, msg: ServerToClient) {
     let mut state = state.lock().unwrap();
 
     match msg {
+        Hello { protocol_version, capabilities } => info!("Server uses protocol version {protocol_version} with {capabilities:?}"),
         UpdateProfiles(profiles) => { state.profiles = profiles; },
         UpdateTimer(timer) => { state.timer = timer; state.timer_updated.notify_one(); }
         UpdateTimerState(timer_state) => if let Some(ref mut timer) = state.timer {
             timer.state = timer_state.deref().clone();
             state.timer_updated.notify_one();
         }
+        UpdateTodos(todos) => if let Some(ref mut timer) = state.timer {
+            timer.todos = todos;
+        }
 
-        RefreshedConfig => todo!(), // show a popup
+        Event(event) => {
+            if let Some(sound) = event_sound(&event) {
+                state.audio_manager.play_logged(sound);
+            }
+            if event == TimerEvent::BlockerLost {
+                state.toasts.push((ToastKind::Warning, "The profile requires a blocker but none is running".to_string()));
+            }
+        }
+        Clients(clients) => { state.clients = clients; }
+
+        History(_) | Stats(_) => (), // the app doesn't ask for these yet
+
+        RefreshedConfig(changes) => info!("Server config reloaded: {changes:?}"), // todo: show a popup
+        ConfigError(diagnostics) => for diagnostic in diagnostics {
+            error!("Invalid server config: {diagnostic}");
+            state.toasts.push((ToastKind::Error, diagnostic.to_string()));
+        }
+
+        Ack { .. } => (), // the app doesn't send request ids
+        Error { kind, message, .. } => {
+            error!("Server error ({kind:?}): {message}");
+            state.toasts.push((ToastKind::Error, message));
+        }
 
         Multiple(_) => panic!(),
     }
 
     if let Some(ref ctx) = state.egui_context {ctx.request_repaint()}
-}
\ No newline at end of file
+}
+fn event_sound(event: &TimerEvent) -> Option<SoundEffects> {
+    Some(match event {
+        TimerEvent::PeriodStarted { period, .. } => match period {
+            PeriodType::Work => SoundEffects::StartWork,
+            PeriodType::ShortBreak => SoundEffects::StartShortBreak,
+            PeriodType::LongBreak => SoundEffects::StartLongBreak,
+            PeriodType::Starting | PeriodType::Uninit => return None,
+        },
+        TimerEvent::PeriodEnded { reason: PeriodEnd::Skipped, .. } => SoundEffects::Skip,
+        TimerEvent::Paused => SoundEffects::Pause,
+        TimerEvent::Resumed => SoundEffects::Unpause,
+        TimerEvent::SessionStopped { finished: true } => SoundEffects::StopFinished,
+        TimerEvent::SessionStopped { finished: false } => SoundEffects::StopUnfinished,
+        TimerEvent::PeriodEnded { .. } | TimerEvent::GoalReached | TimerEvent::BlockerLost | TimerEvent::BlockerReturned => return None,
+    })
+}
@@ -1,5 +1,7 @@
 use crate::{SState, State};
+use common::profile::{Blocking, BlockingMode, ALWAYS_ALLOWED_PROCESSES};
 use pathpatterns::{MatchEntry, MatchList, MatchType, Pattern};
+use std::path::Path;
 
 mod x11;
 
@@ -36,19 +38,46 @@ fn should_enable_blocker(state: &State) -> bool {
 //todo: caching caching caching caching caching
 fn should_block_windows(state: &State, process_name: &str, process_path: Option<&str>) -> bool {
     let blocking = &state.timer.as_ref().unwrap().profile.blocking;
+    let listed = is_listed(blocking, process_name, process_path);
+
+    match blocking.mode {
+        BlockingMode::Denylist => listed,
+        BlockingMode::Allowlist => !listed && !is_always_allowed(process_path),
+    }
+}
+
+fn is_listed(blocking: &Blocking, process_name: &str, process_path: Option<&str>) -> bool {
+    // the server refuses configs with invalid patterns, so the ones that don't compile are skipped
     for str in blocking.window_names.iter() {
         // todo: bad bad bad bad bad
-        if regex::Regex::new(str).unwrap().is_match(process_name) {
+        if regex::Regex::new(str).is_ok_and(|regex| regex.is_match(process_name)) {
             return true;
         }
     }
     if let Some(process_path) = process_path {
         // todo: bad bad bad bad bad
-        let match_list = blocking.process_path.iter().map(|str| MatchEntry::include(Pattern::path(str).unwrap())).collect::<Vec<MatchEntry>>();
+        let match_list = blocking.process_path.iter()
+            .filter_map(|str| Pattern::path(str).ok())
+            .map(MatchEntry::include)
+            .collect::<Vec<MatchEntry>>();
         if match_list.matches(process_path, None) == Some(MatchType::Include) {
             return true;
         }
     }
 
     false
-}
\ No newline at end of file
+}
+
+/// The app itself, the desktop and lock screens.
+/// Windows whose process can't be found are allowed too, many of the desktop's own windows are like that
+fn is_always_allowed(process_path: Option<&str>) -> bool {
+    let Some(process_path) = process_path else { return true };
+    let process_path = Path::new(process_path);
+
+    if std::env::current_exe().is_ok_and(|exe| exe == process_path) {
+        return true;
+    }
+    process_path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| ALWAYS_ALLOWED_PROCESSES.contains(&name))
+}
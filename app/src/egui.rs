@@ -1,10 +1,13 @@
 mod helpers;
 mod create_timer_widget;
+mod profile_editor;
 mod timer_widget;
 mod top_panel;
 
 use crate::SState;
-use eframe::egui::{Context, CentralPanel};
+use eframe::egui::{Context, CentralPanel, Direction};
+use egui_toast::{Toast, ToastOptions, Toasts};
+use std::time::Duration;
 use tracing::{info, instrument};
 
 #[instrument(name = "egui", skip_all)]
@@ -21,8 +24,16 @@ pub fn run(state: SState) {
     .unwrap();
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Screen {
+    #[default]
+    Timer,
+    Profiles,
+}
+
 struct EguiApp {
     state: SState,
+    screen: Screen,
 }
 
 impl EguiApp {
@@ -33,22 +44,37 @@ impl EguiApp {
             state.config.theme.set(cc);
         }
         info!("Starting egui");
-        EguiApp { state }
+        EguiApp { state, screen: Screen::default() }
     }
 }
 
 impl eframe::App for EguiApp {
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
-        let state = self.state.lock().unwrap();
+        let mut state = self.state.lock().unwrap();
 
-        top_panel::panel(ctx, &state);
+        let screen = ctx.screen_rect();
+        let mut toasts = Toasts::new()
+            .anchor((screen.max.x - 10., screen.max.y - 10.))
+            .direction(Direction::BottomUp)
+            .align_to_end(true);
+        for (kind, text) in state.toasts.drain(..) {
+            toasts.add(Toast {
+                text: text.into(),
+                kind,
+                options: ToastOptions::with_duration(Duration::from_secs(5)),
+            });
+        }
+
+        top_panel::panel(ctx, &state, &mut self.screen);
 
         CentralPanel::default().show(ctx, |ui| {
-            if state.timer.is_some() {
-                timer_widget::ui(ui, &state);
-            } else {
-                create_timer_widget::ui(ui, &state);
+            match self.screen {
+                Screen::Profiles => profile_editor::ui(ui, &state),
+                Screen::Timer if state.timer.is_some() => timer_widget::ui(ui, &state),
+                Screen::Timer => create_timer_widget::ui(ui, &state),
             }
         });
+
+        toasts.show(ctx);
     }
 }
\ No newline at end of file
@@ -1,12 +1,12 @@
 use std::sync::Arc;
 use crate::State;
-use crate::egui::helpers::{TOMATO, duration_input_widget};
-use common::timer::TimerGoal;
+use crate::egui::helpers::{TOMATO, duration_input_widget, list_editor};
+use common::timer::{TimerGoal, Todo};
 use common::ws_common::{ClientToServer, ProfileInfo};
 use chrono::Duration;
 use eframe::egui::{Button, ComboBox, DragValue, Grid, RichText, Ui, vec2, Widget};
 use eframe::egui::mutex::Mutex;
-use common::profile::PomodoroSettings;
+use common::profile::{PomodoroSettings, Profile};
 use std::time::Duration as StdDuration;
 
 #[derive(Clone, Debug, Default)]
@@ -14,6 +14,8 @@ pub struct CreateTimerState {
     pub selected_profile: Option<ProfileInfo>,
     pub selected_goal: TimerGoal,
     pub start_in: Option<Duration>,
+    pub todos: Vec<String>,
+    pub new_todo: String,
 }
 
 pub fn ui(ui: &mut Ui, state: &State) {
@@ -28,14 +30,15 @@ pub fn ui(ui: &mut Ui, state: &State) {
         .min_col_width(55.)
         .show(ui, |ui| {
             // ------ Profile ------
-            // in case the profile doesn't exist anymore
-            if data
-                .selected_profile
-                .as_ref()
-                .map(|s| state.profiles.iter().any(|p| p == s))
-                == Some(false)
+            // in case the profile was edited or doesn't exist anymore
+            data.selected_profile = data.selected_profile
+                .take()
+                .and_then(|s| state.profiles.iter().find(|p| **p == s).cloned());
+            // the pomodoro goal needs pomodoro settings
+            if matches!(data.selected_goal, TimerGoal::Pomodoros(_))
+                && data.selected_profile.as_ref().and_then(|p| p.profile.pomodoro.as_ref()).is_none()
             {
-                data.selected_profile = None;
+                data.selected_goal = TimerGoal::None;
             }
             ui.label("Profile:");
 
@@ -61,14 +64,23 @@ pub fn ui(ui: &mut Ui, state: &State) {
                     .selectable_label(matches!(data.selected_goal, TimerGoal::Time(_)), "Time")
                     .clicked()
                 {
-                    let default_dur = data.selected_profile.as_ref().and_then(|p|p.pomodoro.as_ref()).map(|p|p.work_dur).unwrap_or_else(||Duration::minutes(15));
+                    let default_dur = data.selected_profile.as_ref().and_then(|p|p.profile.pomodoro.as_ref()).map(|p|p.work_dur).unwrap_or_else(||Duration::minutes(15));
                     data.selected_goal = TimerGoal::Time(default_dur);
                 }
+                if let Some(pomodoro) = data.selected_profile.as_ref().and_then(|p| p.profile.pomodoro.as_ref()) {
+                    if ui
+                        .selectable_label(matches!(data.selected_goal, TimerGoal::Pomodoros(_)), "Pomodoros")
+                        .clicked()
+                    {
+                        // a full cycle up to the long break
+                        data.selected_goal = TimerGoal::Pomodoros(pomodoro.small_breaks_before_big_one + 1);
+                    }
+                }
                 if ui
-                    .selectable_label(matches!(data.selected_goal, TimerGoal::Todos(_)), "Todos")
+                    .selectable_label(matches!(data.selected_goal, TimerGoal::Todos), "Todos")
                     .clicked()
                 {
-                    data.selected_goal = TimerGoal::Todos(69);
+                    data.selected_goal = TimerGoal::Todos;
                 }
             });
             ui.end_row();
@@ -82,7 +94,7 @@ pub fn ui(ui: &mut Ui, state: &State) {
                         duration_input_widget(ui, duration);
 
                         // if pomodoro is enabled we display the number of pomodoros
-                        if let Some(ProfileInfo{ pomodoro: Some(PomodoroSettings { work_dur, ..}), ..}) = data.selected_profile {
+                        if let Some(ProfileInfo{ profile: Profile { pomodoro: Some(PomodoroSettings { work_dur, ..}), .. }, ..}) = data.selected_profile {
                             let mut pomodoros = duration.num_seconds() as f32 / work_dur.num_seconds() as f32;
                             let mut new_pomodoros = pomodoros;
                             ui.label(" or ");
@@ -107,9 +119,14 @@ pub fn ui(ui: &mut Ui, state: &State) {
                     });
                     ui.end_row();
                 }
-                TimerGoal::Todos(_count) => {
-                    ui.label("todo"); // todo:
-                    ui.label("todo");
+                TimerGoal::Pomodoros(ref mut n) => {
+                    ui.label("Pomodoros:");
+                    DragValue::new(n).speed(0.1).clamp_range(1..=99).suffix(TOMATO).ui(ui);
+                    ui.end_row();
+                }
+                TimerGoal::Todos => {
+                    ui.label("Todos:");
+                    list_editor(ui, &mut data.todos, &mut data.new_todo, "New todo");
                     ui.end_row();
                 }
             }
@@ -131,11 +148,18 @@ pub fn ui(ui: &mut Ui, state: &State) {
             ui.end_row();
 
             // ------ Total time ------
-            if let TimerGoal::Time(time) = data.selected_goal {
+            let work_time = match data.selected_goal {
+                TimerGoal::Time(time) => Some(time),
+                TimerGoal::Pomodoros(n) => data.selected_profile.as_ref()
+                    .and_then(|p| p.profile.pomodoro.as_ref())
+                    .map(|p| p.work_dur * n as i32),
+                _ => None,
+            };
+            if let Some(time) = work_time {
                 let total_time = time
                     + data.start_in.unwrap_or(Duration::zero())
                     + data.selected_profile.as_ref()
-                        .and_then(|p| p.pomodoro.as_ref())
+                        .and_then(|p| p.profile.pomodoro.as_ref())
                         .map(|p|p.calc_break_time(p.calc_pomodoros(time)))
                     .unwrap_or_else(Duration::zero);
                 if total_time != time {
@@ -152,19 +176,22 @@ pub fn ui(ui: &mut Ui, state: &State) {
                 .add_enabled(data.selected_profile.is_some(), Button::new("Start").min_size(vec2(55., 0.)))
                 .clicked()
             {
-                state
-                    .ws_tx
-                    .send(ClientToServer::CreateTimer {
-                        profile_name: data.selected_profile.clone().unwrap().name,
-                        goal: data.selected_goal.clone(),
-                        start_in: data.start_in,
-                    })
-                    .ok();
+                let create = ClientToServer::CreateTimer {
+                    profile_name: data.selected_profile.clone().unwrap().name,
+                    goal: data.selected_goal.clone(),
+                    start_in: data.start_in,
+                };
+                let msg = if data.selected_goal == TimerGoal::Todos {
+                    let todos = data.todos.iter().map(|text| Todo { text: text.clone(), done: false }).collect();
+                    ClientToServer::Multiple(vec![create, ClientToServer::SetTodos(todos)])
+                } else {
+                    create
+                };
+                state.ws_tx.send(msg).ok();
             }
         });
 }
 
-
 fn format_profile_info(pi: &ProfileInfo) -> String {
-    format!("{}{}", pi.name, if pi.pomodoro.is_some() { TOMATO } else { '\0' })
+    format!("{}{}", pi.name, if pi.profile.pomodoro.is_some() { TOMATO } else { '\0' })
 }
\ No newline at end of file
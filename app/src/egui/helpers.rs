@@ -1,5 +1,5 @@
 use std::hash::Hash;
-use eframe::egui::{DragValue, popup_below_widget, Response, Ui, Widget};
+use eframe::egui::{DragValue, Key, popup_below_widget, Response, TextEdit, Ui, Widget};
 use chrono::Duration;
 
 pub const TOMATO: char = match char::from_u32(0x1F345) {
@@ -60,3 +60,30 @@ pub fn duration_input_widget(ui: &mut Ui, dur: &mut Duration) -> Response {
     })
     .response
 }
+
+/// A removable entry per item and a text field to add new ones
+pub fn list_editor(ui: &mut Ui, items: &mut Vec<String>, new_item: &mut String, hint: &str) {
+    ui.vertical(|ui| {
+        let mut to_remove = None;
+        for (i, item) in items.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui.small_button("🗑").clicked() {
+                    to_remove = Some(i);
+                }
+                ui.label(item);
+            });
+        }
+        if let Some(i) = to_remove {
+            items.remove(i);
+        }
+
+        ui.horizontal(|ui| {
+            let response = TextEdit::singleline(new_item).hint_text(hint).desired_width(120.).ui(ui);
+            let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+            if (ui.button("Add").clicked() || submitted) && !new_item.trim().is_empty() {
+                items.push(new_item.trim().to_string());
+                new_item.clear();
+            }
+        });
+    });
+}
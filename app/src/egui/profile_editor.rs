@@ -0,0 +1,163 @@
+use std::sync::Arc;
+use crate::State;
+use crate::egui::helpers::{confirm_popup, duration_input_widget, list_editor};
+use common::profile::{BlockingMode, PomodoroSettings, Profile};
+use common::ws_common::ClientToServer;
+use chrono::Duration;
+use eframe::egui::{Button, DragValue, Grid, ScrollArea, TextEdit, Ui, vec2, Widget};
+use eframe::egui::mutex::Mutex;
+
+#[derive(Clone, Debug, Default)]
+pub struct ProfileEditorState {
+    /// the profile that was loaded into the editor, None for a new one
+    pub editing: Option<String>,
+    pub name: String,
+    pub extends: Vec<String>,
+    pub profile: Profile,
+    pub new_extends: String,
+    pub new_window_name: String,
+    pub new_process_path: String,
+    pub new_website: String,
+}
+
+pub fn ui(ui: &mut Ui, state: &State) {
+    let state_id = ui.id().with("profile_editor");
+    let data = ui.data_mut(|d| {
+        d.get_temp_mut_or_insert_with(state_id, || Arc::new(Mutex::new(ProfileEditorState::default()))).clone()
+    });
+    // we have to deref to avoid borrow checker weirdness
+    let data = &mut *data.lock();
+
+    // ------ Profiles ------
+    ui.horizontal_wrapped(|ui| {
+        for info in &state.profiles {
+            if ui.selectable_label(data.editing.as_ref() == Some(&info.name), info.name.as_str()).clicked() {
+                *data = ProfileEditorState {
+                    editing: Some(info.name.clone()),
+                    name: info.name.clone(),
+                    extends: info.extends.clone(),
+                    profile: info.profile.clone(),
+                    ..Default::default()
+                };
+            }
+        }
+        if ui.button("New").clicked() {
+            *data = ProfileEditorState::default();
+        }
+    });
+    ui.separator();
+
+    ScrollArea::vertical().show(ui, |ui| {
+        Grid::new("profile_editor_grid")
+            .min_col_width(55.)
+            .show(ui, |ui| {
+                ui.label("Name:");
+                TextEdit::singleline(&mut data.name).hint_text("Profile name").ui(ui);
+                ui.end_row();
+
+                // the server only saves what differs from these
+                ui.label("Extends:");
+                list_editor(ui, &mut data.extends, &mut data.new_extends, "Profile name");
+                ui.end_row();
+
+                // ------ Pomodoro ------
+                ui.label("Pomodoro:");
+                let mut pomodoro_enabled = data.profile.pomodoro.is_some();
+                if ui.checkbox(&mut pomodoro_enabled, "").changed() {
+                    data.profile.pomodoro = pomodoro_enabled.then(PomodoroSettings::default);
+                }
+                ui.end_row();
+                if let Some(ref mut pomodoro) = data.profile.pomodoro {
+                    ui.label("Work:");
+                    duration_input_widget(ui, &mut pomodoro.work_dur);
+                    ui.end_row();
+
+                    ui.label("Break:");
+                    duration_input_widget(ui, &mut pomodoro.short_break_dur);
+                    ui.end_row();
+
+                    ui.label("Long break:");
+                    duration_input_widget(ui, &mut pomodoro.long_break_dur);
+                    ui.end_row();
+
+                    ui.label("Breaks before a long one:");
+                    DragValue::new(&mut pomodoro.small_breaks_before_big_one).speed(0.1).clamp_range(1..=99).ui(ui);
+                    ui.end_row();
+                }
+
+                // ------ Permissions ------
+                ui.label("Allow:");
+                ui.vertical(|ui| {
+                    ui.checkbox(&mut data.profile.can_pause, "Pausing");
+                    ui.checkbox(&mut data.profile.can_skip_work, "Skipping work");
+                    ui.checkbox(&mut data.profile.can_stop_before_goal_is_fulfilled, "Stopping before the goal is fulfilled");
+                });
+                ui.end_row();
+
+                ui.label("Emergency unlock:");
+                ui.horizontal(|ui| {
+                    let mut unlock_enabled = data.profile.emergency_unlock_delay.is_some();
+                    if ui.checkbox(&mut unlock_enabled, "").changed() {
+                        data.profile.emergency_unlock_delay = unlock_enabled.then(|| Duration::minutes(10));
+                    }
+                    if let Some(ref mut delay) = data.profile.emergency_unlock_delay {
+                        duration_input_widget(ui, delay);
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Require a blocker:");
+                ui.checkbox(&mut data.profile.requires_blocker, "");
+                ui.end_row();
+
+                // ------ Blocking ------
+                ui.label("Mode:");
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut data.profile.blocking.mode, BlockingMode::Denylist, "Block listed");
+                    ui.selectable_value(&mut data.profile.blocking.mode, BlockingMode::Allowlist, "Only allow listed");
+                });
+                ui.end_row();
+
+                ui.label("Windows:");
+                list_editor(ui, &mut data.profile.blocking.window_names, &mut data.new_window_name, "Regex");
+                ui.end_row();
+
+                ui.label("Processes:");
+                list_editor(ui, &mut data.profile.blocking.process_path, &mut data.new_process_path, "Path pattern");
+                ui.end_row();
+
+                ui.label("Websites:");
+                list_editor(ui, &mut data.profile.blocking.websites, &mut data.new_website, "Website");
+                ui.end_row();
+
+                ui.label("Hide web video:");
+                ui.checkbox(&mut data.profile.blocking.hide_web_video, "");
+                ui.end_row();
+
+                // ------ Buttons ------
+                // the server answers with an error if something is invalid
+                ui.label("");
+                ui.horizontal(|ui| {
+                    let name = data.name.trim().to_string();
+                    let text = if data.editing.as_ref().is_some_and(|editing| *editing != name) { "Save copy" } else { "Save" };
+                    if ui.add_enabled(!name.is_empty(), Button::new(text).min_size(vec2(55., 0.))).clicked() {
+                        state.ws_tx.send(ClientToServer::SaveProfile {
+                            name: name.clone(),
+                            extends: data.extends.clone(),
+                            profile: data.profile.clone(),
+                        }).ok();
+                        data.editing = Some(name);
+                    }
+
+                    if let Some(editing) = data.editing.clone() {
+                        let response = ui.button("Delete");
+                        if confirm_popup(ui, "delete_profile", &response) {
+                            state.ws_tx.send(ClientToServer::DeleteProfile { name: editing }).ok();
+                            *data = ProfileEditorState::default();
+                        }
+                    }
+                });
+                ui.end_row();
+            });
+    });
+}
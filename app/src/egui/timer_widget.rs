@@ -1,5 +1,5 @@
-use chrono::Duration;
-use eframe::egui::{Align, Button, Layout, ProgressBar, RichText, Ui, vec2, Widget};
+use chrono::{Duration, Utc};
+use eframe::egui::{Align, Button, Key, Layout, ProgressBar, RichText, TextEdit, Ui, vec2, Widget};
 use core::time::Duration as StdDuration;
 use crate::egui::helpers::{centerer, confirm_popup, TOMATO};
 use crate::State;
@@ -18,6 +18,13 @@ pub fn ui(ui: &mut Ui, state: &State) {
         goal_info(ui, timer);
 
         buttons(ui, state, timer);
+
+        adjust_buttons(ui, state, timer);
+
+        if timer.goal == TimerGoal::Todos || !timer.todos.is_empty() {
+            ui.separator();
+            todo_list(ui, state, timer);
+        }
     });
 }
 
@@ -78,12 +85,16 @@ fn goal_info(ui: &mut Ui, timer: &Timer) {
                 let Some(ref pomodoro) = timer.profile.pomodoro else {return};
                 // display number of pomodoros
                 ui.label(format!("{}/{}{TOMATO}",
-                                 pomodoro.calc_pomodoros(timer.state.total_dur_worked),
+                                 timer.state.pomodoros_done,
                                  pomodoro.calc_pomodoros(dur)
                 ));
             }
-            TimerGoal::Todos(_) => {
-                ui.label("Todo!"); // todo: display number of todos
+            TimerGoal::Pomodoros(n) => {
+                ui.label(format!("{}/{}{TOMATO}", timer.state.pomodoros_done, n));
+            }
+            TimerGoal::Todos => {
+                let done = timer.todos.iter().filter(|todo| todo.done).count();
+                ui.label(format!("{}/{}✔", done, timer.todos.len()));
             }
         }
     });
@@ -99,12 +110,29 @@ fn buttons(ui: &mut Ui, state: &State, timer: &Timer) {
             state.ws_tx.send(ClientToServer::UnpauseTimer).unwrap();
         }
 
-        // todo: let enabled = timer.profile.can_stop_before_goal_is_fulfilled;
-        let stop_response = ui.add_enabled(true ,Button::new("Stop").min_size(vec2(70.,1.)));
-        if confirm_popup(ui, "stop_confirm_popup" , &stop_response) {
+        let can_stop = timer.can_stop();
+        let stop_response = ui.add_enabled(can_stop, Button::new("Stop").min_size(vec2(70.,1.)));
+        if can_stop && confirm_popup(ui, "stop_confirm_popup" , &stop_response) {
             state.ws_tx.send(ClientToServer::StopTimer).unwrap();
         }
 
+        // a locked session can be unlocked after waiting for the emergency unlock delay
+        if !can_stop && timer.profile.emergency_unlock_delay.is_some() {
+            match timer.state.unlock_at {
+                None => {
+                    let unlock_response = ui.add(Button::new("Unlock").min_size(vec2(70.,1.)));
+                    if confirm_popup(ui, "unlock_confirm_popup", &unlock_response) {
+                        state.ws_tx.send(ClientToServer::RequestEmergencyUnlock).unwrap();
+                    }
+                }
+                Some(unlock_at) => {
+                    let secs = (unlock_at - Utc::now()).num_seconds().max(0);
+                    ui.label(RichText::new(format!("Unlocks in {}:{:0>2}", secs / 60, secs % 60)).color(ui.visuals().weak_text_color()));
+                    ui.ctx().request_repaint_after(StdDuration::from_millis(500));
+                }
+            }
+        }
+
         // skipping only makes sense if pomodoro is enabled or the period is a starting break
         if timer.profile.pomodoro.is_some() || matches!(timer.state.period, PeriodType::Starting) {
             let enabled = timer.profile.can_skip_work || !matches!(timer.state.period, PeriodType::Work);
@@ -115,3 +143,59 @@ fn buttons(ui: &mut Ui, state: &State, timer: &Timer) {
         }
     });
 }
+
+fn adjust_buttons(ui: &mut Ui, state: &State, timer: &Timer) {
+    let has_period_limit = timer.state.progress.limit().is_some();
+    let has_time_goal = matches!(timer.goal, TimerGoal::Time(_));
+    if !has_period_limit && !has_time_goal {
+        return;
+    }
+
+    // separate id so the centerer doesn't share its remembered width with the row above
+    ui.push_id("adjust_buttons", |ui| centerer(ui, |ui| {
+        if has_period_limit {
+            let can_shorten = timer.profile.can_skip_work || timer.state.period != PeriodType::Work;
+            if ui.add_enabled(can_shorten, Button::new("-5m")).on_hover_text("Shorten the period").clicked() {
+                state.ws_tx.send(ClientToServer::AdjustPeriod { by: Duration::minutes(-5) }).unwrap();
+            }
+            // the server refuses to extend breaks in profiles that don't allow pausing
+            let can_extend = timer.profile.can_pause || timer.state.period == PeriodType::Work;
+            if ui.add_enabled(can_extend, Button::new("+5m")).on_hover_text("Extend the period").clicked() {
+                state.ws_tx.send(ClientToServer::AdjustPeriod { by: Duration::minutes(5) }).unwrap();
+            }
+        }
+
+        if has_time_goal {
+            let can_shorten = timer.profile.can_stop_before_goal_is_fulfilled;
+            if ui.add_enabled(can_shorten, Button::new("-15m")).on_hover_text("Shorten the goal").clicked() {
+                state.ws_tx.send(ClientToServer::AdjustGoal { by: Duration::minutes(-15) }).unwrap();
+            }
+            if ui.button("+15m").on_hover_text("Extend the goal").clicked() {
+                state.ws_tx.send(ClientToServer::AdjustGoal { by: Duration::minutes(15) }).unwrap();
+            }
+        }
+    }));
+}
+
+fn todo_list(ui: &mut Ui, state: &State, timer: &Timer) {
+    ui.with_layout(Layout::top_down(Align::Min), |ui| {
+        for (index, todo) in timer.todos.iter().enumerate() {
+            let mut done = todo.done;
+            if ui.checkbox(&mut done, &todo.text).changed() {
+                state.ws_tx.send(ClientToServer::CompleteTodo { index, done }).unwrap();
+            }
+        }
+
+        let id = ui.id().with("new_todo");
+        let mut new_todo = ui.data_mut(|d| d.get_temp::<String>(id)).unwrap_or_default();
+        ui.horizontal(|ui| {
+            let response = TextEdit::singleline(&mut new_todo).hint_text("New todo").ui(ui);
+            let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+            if (ui.button("Add").clicked() || submitted) && !new_todo.trim().is_empty() {
+                state.ws_tx.send(ClientToServer::AddTodo(new_todo.trim().to_string())).unwrap();
+                new_todo.clear();
+            }
+        });
+        ui.data_mut(|d| d.insert_temp(id, new_todo));
+    });
+}
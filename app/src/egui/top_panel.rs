@@ -1,10 +1,11 @@
 use chrono::{Utc};
 use crate::State;
+use crate::egui::Screen;
 use eframe::egui::{Align, CollapsingHeader, Color32, Context, Label, Layout, popup_below_widget, Response, RichText, ScrollArea, Sense, TextStyle, TopBottomPanel, Ui};
 use eframe::egui::special_emojis::GITHUB;
 use crate::audio_manager::SoundEffects;
 
-pub fn panel(ctx: &Context, state: &State) {
+pub fn panel(ctx: &Context, state: &State, screen: &mut Screen) {
     TopBottomPanel::top("top")
         .min_height(0.)
         .show(ctx, |ui| {
@@ -14,6 +15,8 @@ pub fn panel(ctx: &Context, state: &State) {
 
                 ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                     ui.hyperlink_to(GITHUB.to_string(), "https://www.youtube.com/watch?v=dQw4w9WgXcQ");
+                    ui.selectable_value(screen, Screen::Profiles, "Profiles");
+                    ui.selectable_value(screen, Screen::Timer, "Timer");
                 });
             });
         });
@@ -46,6 +49,23 @@ fn popup(ui: &mut Ui, logo_response: &Response, state: &State) {
             ui.ctx().set_debug_on_hover(debug_on_hover);
         }
 
+        CollapsingHeader::new("Clients").default_open(true).show(ui, |ui| {
+            let utc = Utc::now();
+            for client in state.clients.iter() {
+                let text = format!(
+                    "  #{} {:?} {} (protocol {}), connected {}s ago{}{}",
+                    client.id,
+                    client.kind,
+                    client.version.as_deref().unwrap_or("?"),
+                    client.protocol_version,
+                    (utc - client.connected_at).num_seconds(),
+                    if client.alive { String::new() } else { format!(", no heartbeat for {}s", (utc - client.last_heartbeat).num_seconds()) },
+                    if client.is_blocker() { ", blocker" } else { "" },
+                );
+                ui.label(RichText::new(text).color(if client.alive {Color32::LIGHT_GRAY} else {Color32::RED}));
+            }
+        });
+
         CollapsingHeader::new("Detected windows").default_open(true).show(ui, |ui| {
             let utc = Utc::now();
             for (name, (time, blocked, extra)) in state.detected_windows.iter() {
@@ -5,16 +5,19 @@ mod client_config;
 mod audio_manager;
 
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
 use chrono::{DateTime, Utc};
 use eframe::egui::Context;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
 use tokio::sync::Notify;
-use common::register_tracing;
+use common::{get_override, register_tracing};
 use crate::client_config::ClientConfig;
 use common::timer::Timer;
-use common::ws_common::{ClientToServer, ProfileInfo};
+use common::ws_common::{ClientInfo, ClientToServer, ProfileInfo};
 use anyhow::Result;
+use egui_toast::ToastKind;
+use tracing::error;
 use crate::audio_manager::AudioManager;
 
 pub type SState = Arc<Mutex<State>>;
@@ -29,20 +32,34 @@ pub struct State {
     pub ws_tx: UnboundedSender<ClientToServer>,
     pub egui_context: Option<Context>,
     pub audio_manager: AudioManager,
+    /// shown and cleared on the next frame
+    pub toasts: Vec<(ToastKind, String)>,
 
     // for use in the secret debug menu
+    pub clients: Vec<ClientInfo>,
     //                              \/ title               \/ blocked    \/ extra info
     pub detected_windows: HashMap<String, (DateTime<Utc>, bool, Option<Vec<String>>)>,
 }
 
 #[tokio::main]
 pub async fn main() -> Result<()> {
-    register_tracing("127.0.0.1:6670");
+    // loaded before tracing is registered because it has the console address
+    let config = client_config::load_config();
+    let console_address = get_override("console-address", "WATCHWAH_CONSOLE_ADDRESS")
+        .or_else(|| config.as_ref().ok().and_then(|config| config.console_address))
+        .unwrap_or_else(|| SocketAddr::from(([127, 0, 0, 1], 6670)));
+    register_tracing(console_address);
+
+    let mut config = config.unwrap_or_else(|e| {
+        error!("Unable to load client.toml, using the default config: {e}");
+        ClientConfig::default()
+    });
+    config.server_url = get_override("server-url", "WATCHWAH_SERVER_URL").or(config.server_url);
 
     // state
     let (ws_tx,ws_rx) = unbounded_channel::<ClientToServer>();
     let state = Arc::new(Mutex::new(State{
-        config: client_config::load_config(),
+        config,
 
         profiles: vec![],
         timer: None,
@@ -52,7 +69,9 @@ pub async fn main() -> Result<()> {
         ws_tx,
         egui_context: None,
         audio_manager: AudioManager::new()?,
+        toasts: vec![],
 
+        clients: vec![],
         detected_windows: HashMap::new(),
     }));
 
@@ -70,8 +89,6 @@ pub async fn main() -> Result<()> {
     Ok(())
 }
 
-//todo: sooound
-//todo: toast popup
 //todo: notifications when a period is over/starting
 //todo: skip period button?
 //todo: finish the ui
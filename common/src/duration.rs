@@ -0,0 +1,99 @@
+use chrono::Duration;
+use serde::de::{self, Visitor};
+use serde::{Deserializer, Serializer};
+use serde_with::{DeserializeAs, SerializeAs};
+use std::fmt::Formatter;
+
+/// For durations in config files: seconds, or a string like "25m", "1h30m" or "90s".
+/// Serialized as seconds so clients that only know the old format keep working
+pub struct HumanDuration;
+
+impl SerializeAs<Duration> for HumanDuration {
+    fn serialize_as<S: Serializer>(source: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(source.num_seconds())
+    }
+}
+
+impl<'de> DeserializeAs<'de, Duration> for HumanDuration {
+    fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        deserializer.deserialize_any(HumanDurationVisitor)
+    }
+}
+
+struct HumanDurationVisitor;
+
+impl<'de> Visitor<'de> for HumanDurationVisitor {
+    type Value = Duration;
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        f.write_str("seconds or a duration like \"25m\"")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Duration, E> {
+        // Duration::seconds panics past what fits in milliseconds
+        const MAX_SECONDS: i64 = i64::MAX / 1000;
+        if !(-MAX_SECONDS..=MAX_SECONDS).contains(&v) {
+            return Err(E::custom(format!("{v} seconds is too long a duration")));
+        }
+        Ok(Duration::seconds(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Duration, E> {
+        self.visit_i64(i64::try_from(v).map_err(E::custom)?)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Duration, E> {
+        let dur = humantime::parse_duration(v).map_err(|e| E::custom(format!("invalid duration {v:?}: {e}")))?;
+        Duration::from_std(dur).map_err(E::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::value::{Error, I64Deserializer, StrDeserializer, U64Deserializer};
+
+    fn from_int(v: i64) -> Result<Duration, Error> {
+        HumanDuration::deserialize_as(I64Deserializer::new(v))
+    }
+
+    fn from_str(v: &str) -> Result<Duration, Error> {
+        HumanDuration::deserialize_as(StrDeserializer::new(v))
+    }
+
+    #[test]
+    fn integers_are_seconds() {
+        assert_eq!(from_int(1500).unwrap(), Duration::minutes(25));
+        assert_eq!(HumanDuration::deserialize_as(U64Deserializer::<Error>::new(1500)).unwrap(), Duration::minutes(25));
+    }
+
+    #[test]
+    fn negative_integers_are_left_to_validation() {
+        assert_eq!(from_int(-30).unwrap(), Duration::seconds(-30));
+    }
+
+    #[test]
+    fn strings_are_parsed() {
+        assert_eq!(from_str("25m").unwrap(), Duration::minutes(25));
+        assert_eq!(from_str("1h30m").unwrap(), Duration::minutes(90));
+        assert_eq!(from_str("90s").unwrap(), Duration::seconds(90));
+    }
+
+    #[test]
+    fn invalid_strings_are_rejected() {
+        let e = from_str("soon").unwrap_err();
+        assert!(e.to_string().contains("invalid duration \"soon\""), "{e}");
+        assert!(from_str("-5m").is_err());
+        assert!(from_str("").is_err());
+    }
+
+    #[test]
+    fn too_large_integers_are_rejected() {
+        assert!(HumanDuration::deserialize_as(U64Deserializer::<Error>::new(u64::MAX)).is_err());
+        assert!(HumanDuration::deserialize_as(U64Deserializer::<Error>::new(i64::MAX as u64)).is_err());
+        assert!(from_int(i64::MAX).is_err());
+        assert!(from_int(i64::MIN).is_err());
+        assert!(from_int(-(i64::MAX / 1000) - 1).is_err());
+        assert_eq!(from_int(i64::MAX / 1000).unwrap(), Duration::seconds(i64::MAX / 1000));
+    }
+}
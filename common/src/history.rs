@@ -0,0 +1,36 @@
+use crate::timer::{PeriodType, TimerGoal};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DurationSeconds};
+
+/// Something that happened to a period, recorded by the server
+#[serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub time: DateTime<Utc>,
+    pub profile: String,
+    pub goal: TimerGoal,
+    pub period: PeriodType,
+
+    /// the limit the period was started with
+    #[serde_as(as = "Option<DurationSeconds<i64>>")]
+    pub planned: Option<Duration>,
+    /// time spent in the period up to this entry, pauses excluded
+    #[serde_as(as = "DurationSeconds<i64>")]
+    pub actual: Duration,
+    pub ended: PeriodEnd,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum PeriodEnd {
+    /// the period ran out
+    Finished,
+    /// the period ran out and with it the goal was reached
+    GoalReached,
+    Skipped,
+    /// the period is not over, it will get another entry when it ends
+    Paused,
+    Stopped,
+    /// the profile requires a blocker and none was alive, the period goes on like after [PeriodEnd::Paused]
+    BlockerLost,
+}
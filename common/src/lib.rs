@@ -1,17 +1,23 @@
+pub mod duration;
+pub mod history;
 pub mod profile;
+pub mod stats;
 pub mod timer;
 pub mod ws_common;
 
+use std::fmt::Display;
 use std::net::SocketAddr;
+use std::str::FromStr;
 use console_subscriber::ConsoleLayer;
 use tracing_subscriber::{EnvFilter, Layer};
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 use std::path::{Path, PathBuf};
 
-pub fn register_tracing(addr: &str) {
+/// `console_addr` is where tokio-console can connect
+pub fn register_tracing(console_addr: SocketAddr) {
     tracing_subscriber::registry()
-        .with(ConsoleLayer::builder().server_addr(addr.parse::<SocketAddr>().unwrap()).with_default_env().spawn())
+        .with(ConsoleLayer::builder().server_addr(console_addr).with_default_env().spawn())
         .with(tracing_subscriber::fmt::layer().with_filter(
             EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()),
         ))
@@ -23,3 +29,52 @@ pub fn get_config_path() -> PathBuf {
         .join(".config")
         .join("watchwah")
 }
+
+/// Directory for data the daemon keeps between runs, `$XDG_STATE_HOME/watchwah` or `~/.local/state/watchwah`
+pub fn get_state_path() -> PathBuf {
+    match std::env::var("XDG_STATE_HOME") {
+        Ok(path) if !path.is_empty() => Path::new(&path).join("watchwah"),
+        _ => Path::new(&std::env::var("HOME").unwrap())
+            .join(".local")
+            .join("state")
+            .join("watchwah"),
+    }
+}
+
+/// Directory for files that only matter while the user is logged in, `$XDG_RUNTIME_DIR` or the state directory
+pub fn get_runtime_path() -> PathBuf {
+    match std::env::var("XDG_RUNTIME_DIR") {
+        Ok(path) if !path.is_empty() => PathBuf::from(path),
+        _ => get_state_path(),
+    }
+}
+
+/// The server writes the address it's listening on to this file so clients can find it
+pub fn get_discovery_path() -> PathBuf {
+    get_runtime_path().join("watchwah.addr")
+}
+
+/// Value of `--name value` or `--name=value` on the command line, otherwise of the `env` variable.
+/// Panics if the value can't be parsed as this only runs at startup
+pub fn get_override<T: FromStr>(name: &str, env: &str) -> Option<T>
+where
+    T::Err: Display,
+{
+    let flag = format!("--{name}");
+    let mut args = std::env::args().skip(1);
+    let from_args = loop {
+        let Some(arg) = args.next() else { break None };
+        if arg == flag {
+            break args.next();
+        }
+        if let Some(value) = arg.strip_prefix(&flag).and_then(|rest| rest.strip_prefix('=')) {
+            break Some(value.to_string());
+        }
+    };
+
+    let value = from_args.or_else(|| std::env::var(env).ok().filter(|v| !v.is_empty()))?;
+    match value.parse() {
+        Ok(value) => Some(value),
+        Err(e) => panic!("Invalid value \"{value}\" for {flag}/{env}: {e}"),
+    }
+}
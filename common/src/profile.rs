@@ -1,11 +1,16 @@
 use chrono::Duration;
 use serde::{Deserialize, Serialize};
-use serde_with::{serde_as, DurationSeconds};
+use serde_with::serde_as;
+use crate::duration::HumanDuration;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Profile {
     #[serde(skip, default)] // generated from the file name
     pub name: String,
+    /// from the file's `extends`, their values are already merged in
+    #[serde(skip, default)]
+    pub extends: Vec<String>,
 
     pub pomodoro: Option<PomodoroSettings>,
     #[serde(default)]
@@ -16,22 +21,45 @@ pub struct Profile {
     pub can_pause: bool,
     #[serde(default)]
     pub can_skip_work: bool,
+    /// if set, a session that can't be stopped yet can be unlocked by waiting this long after requesting it
+    #[serde(default)]
+    #[serde_as(as = "Option<HumanDuration>")]
+    pub emergency_unlock_delay: Option<Duration>,
+    /// warn and record it in the history if no blocker client is alive during a work period
+    #[serde(default)]
+    pub requires_blocker: bool,
 }
 fn can_stop_before_goal_is_fulfilled_default() -> bool { true }
 fn can_pause_default() -> bool { true }
 
-// todo: use a better format than DurationSeconds
+/// Same as an empty profile file
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            extends: vec![],
+            pomodoro: None,
+            blocking: Blocking::default(),
+            can_stop_before_goal_is_fulfilled: can_stop_before_goal_is_fulfilled_default(),
+            can_pause: can_pause_default(),
+            can_skip_work: false,
+            emergency_unlock_delay: None,
+            requires_blocker: false,
+        }
+    }
+}
+
 #[serde_as]
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct PomodoroSettings {
     #[serde(default = "work_dur_default")]
-    #[serde_as(as = "DurationSeconds<i64>")]
+    #[serde_as(as = "HumanDuration")]
     pub work_dur: Duration,
     #[serde(default = "small_breaks_default")]
-    #[serde_as(as = "DurationSeconds<i64>")]
+    #[serde_as(as = "HumanDuration")]
     pub short_break_dur: Duration,
     #[serde(default = "long_breaks_default")]
-    #[serde_as(as = "DurationSeconds<i64>")]
+    #[serde_as(as = "HumanDuration")]
     pub long_break_dur: Duration,
     #[serde(default = "break_ratio_default")]
     pub small_breaks_before_big_one: u32,
@@ -41,6 +69,17 @@ fn small_breaks_default() -> Duration { Duration::minutes(5) }
 fn long_breaks_default() -> Duration { Duration::minutes(15) }
 fn break_ratio_default() -> u32 { 3 }
 
+impl Default for PomodoroSettings {
+    fn default() -> Self {
+        Self {
+            work_dur: work_dur_default(),
+            short_break_dur: small_breaks_default(),
+            long_break_dur: long_breaks_default(),
+            small_breaks_before_big_one: break_ratio_default(),
+        }
+    }
+}
+
 impl PomodoroSettings {
     pub fn calc_pomodoros(&self, work_time: Duration) -> u32 {
         u32::try_from((work_time.num_seconds() as f32 / self.work_dur.num_seconds() as f32).ceil() as i32 ).unwrap_or(0)
@@ -54,7 +93,7 @@ impl PomodoroSettings {
 }
 
 // todo: rename
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
 pub struct Blocking {
     // regex
     #[serde(default)]
@@ -66,4 +105,43 @@ pub struct Blocking {
     pub websites: Vec<String>,
     #[serde(default)]
     pub hide_web_video: bool,
-}
\ No newline at end of file
+    /// whether the lists above are blocked or the only things that aren't
+    #[serde(default)]
+    pub mode: BlockingMode,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum BlockingMode {
+    #[default]
+    Denylist,
+    /// everything else is blocked, except for [ALWAYS_ALLOWED_PROCESSES] and windows whose process is unknown
+    Allowlist,
+}
+
+/// Executable names that are never blocked in allowlist mode, so the desktop and the lock screen keep working
+pub const ALWAYS_ALLOWED_PROCESSES: &[&str] = &[
+    // desktops and panels
+    "plasmashell",
+    "gnome-shell",
+    "xfdesktop",
+    "xfce4-panel",
+    "nemo-desktop",
+    "pcmanfm",
+    "pcmanfm-qt",
+    "lxqt-panel",
+    "mate-panel",
+    "caja",
+    "polybar",
+    "waybar",
+    // lock screens
+    "kscreenlocker_greet",
+    "gnome-screensaver",
+    "cinnamon-screensaver",
+    "mate-screensaver",
+    "xscreensaver",
+    "light-locker",
+    "i3lock",
+    "swaylock",
+    "slock",
+    "xsecurelock",
+];
\ No newline at end of file
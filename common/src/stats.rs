@@ -0,0 +1,155 @@
+use crate::history::{HistoryEntry, PeriodEnd};
+use crate::timer::PeriodType;
+use chrono::{Datelike, Duration, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DurationSeconds};
+use std::collections::BTreeMap;
+
+/// Aggregates computed from the history
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Stats {
+    pub total: Totals,
+    pub days: Vec<DayStats>,
+    pub weeks: Vec<WeekStats>,
+    pub profiles: Vec<ProfileStats>,
+
+    /// most consecutive days on which a goal was reached
+    pub longest_streak: u32,
+    /// consecutive days on which a goal was reached, ending today or yesterday
+    pub current_streak: u32,
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Totals {
+    #[serde_as(as = "DurationSeconds<i64>")]
+    pub worked: Duration,
+    /// work periods that ran out
+    pub pomodoros_finished: u32,
+    pub pomodoros_skipped: u32,
+    pub pauses: u32,
+    pub goals_reached: u32,
+    pub sessions_stopped: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DayStats {
+    pub day: NaiveDate,
+    pub totals: Totals,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WeekStats {
+    /// ISO year and week number
+    pub year: i32,
+    pub week: u32,
+    pub totals: Totals,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProfileStats {
+    pub profile: String,
+    pub totals: Totals,
+}
+
+impl Default for Totals {
+    fn default() -> Self {
+        Self {
+            worked: Duration::zero(),
+            pomodoros_finished: 0,
+            pomodoros_skipped: 0,
+            pauses: 0,
+            goals_reached: 0,
+            sessions_stopped: 0,
+        }
+    }
+}
+
+impl Totals {
+    fn add(&mut self, entry: &HistoryEntry) {
+        use PeriodEnd::*;
+
+        if entry.period == PeriodType::Work {
+            // paused entries are followed by another one for the same period, so we don't count them
+            if !matches!(entry.ended, Paused | BlockerLost) {
+                self.worked = self.worked + entry.actual;
+            }
+
+            match entry.ended {
+                Finished | GoalReached => self.pomodoros_finished += 1,
+                Skipped => self.pomodoros_skipped += 1,
+                _ => {}
+            }
+        }
+
+        match entry.ended {
+            Paused => self.pauses += 1,
+            GoalReached => self.goals_reached += 1,
+            Stopped => self.sessions_stopped += 1,
+            _ => {}
+        }
+    }
+
+    /// ratio of work periods that weren't skipped
+    pub fn completion_rate(&self) -> Option<f32> {
+        let total = self.pomodoros_finished + self.pomodoros_skipped;
+        (total != 0).then(|| self.pomodoros_finished as f32 / total as f32)
+    }
+}
+
+impl Stats {
+    /// Days and weeks are in local time
+    pub fn compute(entries: &[HistoryEntry]) -> Stats {
+        let mut total = Totals::default();
+        let mut days = BTreeMap::<NaiveDate, Totals>::new();
+        let mut weeks = BTreeMap::<(i32, u32), Totals>::new();
+        let mut profiles = BTreeMap::<&str, Totals>::new();
+
+        for entry in entries {
+            let day = entry.time.with_timezone(&Local).date_naive();
+            let week = day.iso_week();
+
+            total.add(entry);
+            days.entry(day).or_default().add(entry);
+            weeks.entry((week.year(), week.week())).or_default().add(entry);
+            profiles.entry(&entry.profile).or_default().add(entry);
+        }
+
+        let (longest_streak, current_streak) = streaks(&days, Local::now().date_naive());
+
+        Stats {
+            total,
+            days: days.into_iter().map(|(day, totals)| DayStats { day, totals }).collect(),
+            weeks: weeks.into_iter().map(|((year, week), totals)| WeekStats { year, week, totals }).collect(),
+            profiles: profiles.into_iter().map(|(profile, totals)| ProfileStats { profile: profile.to_string(), totals }).collect(),
+            longest_streak,
+            current_streak,
+        }
+    }
+}
+
+/// returns the longest and the current streak
+fn streaks(days: &BTreeMap<NaiveDate, Totals>, today: NaiveDate) -> (u32, u32) {
+    let mut longest = 0;
+    let mut current = 0;
+    let mut last_day: Option<NaiveDate> = None;
+
+    for (&day, totals) in days {
+        if totals.goals_reached == 0 {
+            continue;
+        }
+
+        current = match last_day {
+            Some(last_day) if day - last_day == Duration::days(1) => current + 1,
+            _ => 1,
+        };
+        longest = longest.max(current);
+        last_day = Some(day);
+    }
+
+    // the current streak is broken if the last successful day was before yesterday
+    match last_day {
+        Some(last_day) if today - last_day <= Duration::days(1) => (longest, current),
+        _ => (longest, 0),
+    }
+}
@@ -2,39 +2,91 @@ use crate::profile::Profile;
 use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DurationSeconds};
+use crate::duration::HumanDuration;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Timer {
-    // immutable
+    // immutable, except for the profile getting refreshed when the config is reloaded
     pub profile: Profile,
     pub goal: TimerGoal,
 
     // mutable
     pub state: TimerState,
+    #[serde(default)]
+    pub todos: Vec<Todo>,
+}
+
+impl Timer {
+    /// true if there is at least one todo and all of them are done
+    pub fn todos_completed(&self) -> bool {
+        !self.todos.is_empty() && self.todos.iter().all(|todo| todo.done)
+    }
+
+    /// whether the profile allows stopping the timer right now
+    pub fn can_stop(&self) -> bool {
+        self.profile.can_stop_before_goal_is_fulfilled
+            || TimerGoal::is_fulfilled(self)
+            || self.state.unlock_at.is_some_and(|unlock_at| unlock_at <= Utc::now())
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Todo {
+    pub text: String,
+    #[serde(default)]
+    pub done: bool,
 }
 
 #[serde_as]
 #[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
 pub enum TimerGoal {
     #[default] None,
-    Todos(u32),
+    /// the timer stops once all of its todos are done
+    Todos,
 
-    Time(#[serde_as(as = "DurationSeconds<i64>")] Duration),
-    //Pomodoros(u32),
+    Time(#[serde_as(as = "HumanDuration")] Duration),
+    /// number of work periods that have to run out, requires pomodoro settings
+    Pomodoros(u32),
 }
 
 impl TimerGoal {
     /// total time limit
     pub fn time_limit(timer: &Timer) -> Option<Duration> {
         match timer.goal {
-            TimerGoal::None | TimerGoal::Todos(_) => None,
+            TimerGoal::None | TimerGoal::Todos => None,
             TimerGoal::Time(dur) => Some(dur),
+            TimerGoal::Pomodoros(n) => timer.profile.pomodoro.as_ref().map(|p| p.work_dur * n as i32),
         }
     }
 
     // time left of the time limit
     pub fn time_left(timer: &Timer) -> Option<Duration> {
-        TimerGoal::time_limit(timer).map(|dur| dur - timer.state.total_dur_worked)
+        match timer.goal {
+            // pomodoros that didn't run out are left, no matter how much time was spent in them
+            TimerGoal::Pomodoros(n) => timer.profile.pomodoro.as_ref()
+                .map(|p| p.work_dur * n.saturating_sub(timer.state.pomodoros_done) as i32),
+            _ => TimerGoal::time_limit(timer).map(|dur| dur - timer.state.total_dur_worked),
+        }
+    }
+
+    /// whether the goal has been fulfilled so far, sessions without a goal are always fulfilled
+    pub fn is_fulfilled(timer: &Timer) -> bool {
+        match timer.goal {
+            TimerGoal::None => true,
+            TimerGoal::Todos => timer.todos_completed(),
+            TimerGoal::Time(dur) => timer.state.dur_worked() >= dur,
+            TimerGoal::Pomodoros(n) => timer.state.pomodoros_done >= n,
+        }
+    }
+
+    /// whether the timer should stop, only meaningful at the end of a period
+    pub fn is_reached(timer: &Timer) -> bool {
+        match timer.goal {
+            TimerGoal::None => false,
+            TimerGoal::Todos => timer.todos_completed(),
+            TimerGoal::Time(_) => TimerGoal::time_left(timer).is_some_and(|left| left <= Duration::zero()),
+            TimerGoal::Pomodoros(n) => timer.state.pomodoros_done >= n,
+        }
     }
 }
 
@@ -49,9 +101,26 @@ pub struct TimerState {
     pub total_dur_worked: Duration,
     /// small breaks since the last long break
     pub small_breaks: u32,
+    /// work periods that ran out, skipped ones aren't counted
+    #[serde(default)]
+    pub pomodoros_done: u32,
+    /// when the requested emergency unlock takes effect
+    #[serde(default)]
+    pub unlock_at: Option<DateTime<Utc>>,
+    /// set when the profile requires a blocker and none was alive during the current work period
+    #[serde(default)]
+    pub blocker_lost: bool,
 }
 
 impl TimerState {
+    /// like total_dur_worked, but only counts the part of the current work period that already passed
+    pub fn dur_worked(&self) -> Duration {
+        match (self.period, self.progress.limit()) {
+            (PeriodType::Work, Some(limit)) => self.total_dur_worked - (limit - self.progress.elapsed()).max(Duration::zero()),
+            _ => self.total_dur_worked,
+        }
+    }
+
     pub fn should_block(&self) -> bool {
         self.progress.is_running() && matches!(self.period, PeriodType::Work)
     }
@@ -91,9 +160,14 @@ impl PeriodProgress {
     }
 
     pub fn elapsed(&self) -> Duration {
+        self.elapsed_at(Utc::now())
+    }
+
+    /// elapsed time as it was/will be at the given moment
+    pub fn elapsed_at(&self, time: DateTime<Utc>) -> Duration {
         match self {
             Uninit => Duration::zero(),
-            Running { elapsed, start, .. } => *elapsed + (Utc::now() - *start),
+            Running { elapsed, start, .. } => *elapsed + (time - *start),
             Paused { elapsed, .. } => *elapsed,
         }
     }
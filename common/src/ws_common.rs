@@ -1,15 +1,42 @@
-use chrono::Duration;
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use serde_with::DurationSeconds;
-use crate::profile::PomodoroSettings;
+use std::fmt::{Display, Formatter};
+use crate::history::{HistoryEntry, PeriodEnd};
+use crate::profile::{PomodoroSettings, Profile};
+use crate::stats::Stats;
 
-use crate::timer::{Timer, TimerGoal, TimerState};
+use crate::timer::{PeriodType, Timer, TimerGoal, TimerState, Todo};
+
+/// Bumped when a message changes in a way older clients can't handle
+pub const PROTOCOL_VERSION: u32 = 1;
+/// Oldest client protocol version the server still talks to
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// How often clients send [ClientToServer::Heartbeat]
+pub const HEARTBEAT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+/// A client that hasn't sent a heartbeat for this long is considered dead, even if its connection is still open
+pub const HEARTBEAT_TIMEOUT_SECS: i64 = 30;
 
 #[serde_as]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[must_use]
 pub enum ClientToServer {
+    /// has to be the first message, the server answers with [ServerToClient::Hello]
+    Hello {
+        protocol_version: u32,
+        client_kind: ClientKind,
+        /// version of the client program, shown to other clients
+        #[serde(default)]
+        client_version: Option<String>,
+        #[serde(default)]
+        capabilities: Vec<Capability>,
+    },
+    /// has to follow [ClientToServer::Hello] if the server has a key set
+    Authenticate { key: String },
+    /// sent every [HEARTBEAT_INTERVAL] to show the client is still working
+    Heartbeat,
     CreateTimer {
         goal: TimerGoal,
         profile_name: String,
@@ -20,29 +47,231 @@ pub enum ClientToServer {
     UnpauseTimer,
     StopTimer,
     SkipPeriod,
+    /// lets the timer be stopped after the profile's emergency unlock delay
+    RequestEmergencyUnlock,
+    /// lengthens the time goal, or shortens it if negative
+    AdjustGoal {
+        #[serde_as(as = "DurationSeconds<i64>")]
+        by: Duration,
+    },
+    /// lengthens the current period, or shortens it if negative
+    AdjustPeriod {
+        #[serde_as(as = "DurationSeconds<i64>")]
+        by: Duration,
+    },
+
+    /// answered with a [ServerToClient::History] sent only to the client that asked
+    QueryHistory {
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        profile: Option<String>,
+    },
+    /// answered with a [ServerToClient::Stats] computed from the same entries as [ClientToServer::QueryHistory]
+    QueryStats {
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        profile: Option<String>,
+    },
+
+    SetTodos(Vec<Todo>),
+    AddTodo(String),
+    /// marks the todo at the index as done or not done
+    CompleteTodo {
+        index: usize,
+        done: bool,
+    },
+
+    /// validated and written to `profiles/<name>.toml`, replacing the whole file. The profiles are sent again once the
+    /// server reloaded them. Only what differs from the profiles in `extends` is written, so changes to them still apply
+    SaveProfile {
+        name: String,
+        #[serde(default)]
+        extends: Vec<String>,
+        profile: Profile,
+    },
+    /// refused while other profiles extend it
+    DeleteProfile { name: String },
 
-    // todo: SetTodos,
     Multiple(Vec<ClientToServer>),
+    /// the server answers with [ServerToClient::Ack] or [ServerToClient::Error] carrying the same id
+    Request { id: u64, msg: Box<ClientToServer> },
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[must_use]
 pub enum ServerToClient {
+    /// the capabilities both sides support
+    Hello { protocol_version: u32, capabilities: Vec<Capability> },
     UpdateProfiles(Vec<ProfileInfo>),
     UpdateTimer(Option<Box<Timer>>),
     UpdateTimerState(Box<TimerState>),
+    History(Vec<HistoryEntry>),
+    Stats(Box<Stats>),
+    UpdateTodos(Vec<Todo>),
+    RefreshedConfig(ConfigChanges),
+    /// the config couldn't be reloaded, the server keeps using the last good one
+    ConfigError(Vec<ConfigDiagnostic>),
+    /// sent after the timer update it caused, only to clients with [Capability::Events]
+    Event(TimerEvent),
+    /// the clients that finished the handshake, sent to clients with [Capability::Clients] whenever one connects,
+    /// disconnects, stops sending heartbeats or starts again
+    Clients(Vec<ClientInfo>),
 
-    // todo: UpdateTodos
-    RefreshedConfig,
+    /// only sent to the client that made the request
+    Ack { id: u64 },
+    /// only sent to the client whose message failed, id is None if the message wasn't a [ClientToServer::Request]
+    Error { id: Option<u64>, kind: ErrorKind, message: String },
 
     Multiple(Vec<ServerToClient>),
 }
 
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ClientKind {
+    App,
+    Extension,
+    Cli,
+    #[serde(other)]
+    Other,
+}
+
+/// A connected client as seen by the server
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClientInfo {
+    /// unique while the server runs
+    pub id: u64,
+    pub kind: ClientKind,
+    pub version: Option<String>,
+    pub protocol_version: u32,
+    pub capabilities: Vec<Capability>,
+    pub connected_at: DateTime<Utc>,
+    /// only as recent as the last [ServerToClient::Clients], which isn't sent for every heartbeat
+    pub last_heartbeat: DateTime<Utc>,
+    /// whether a heartbeat arrived in the last [HEARTBEAT_TIMEOUT_SECS], kept up to date by the server
+    pub alive: bool,
+}
+
+impl ClientInfo {
+    pub fn is_alive(&self, now: DateTime<Utc>) -> bool {
+        now - self.last_heartbeat < Duration::seconds(HEARTBEAT_TIMEOUT_SECS)
+    }
+
+    pub fn is_blocker(&self) -> bool {
+        self.capabilities.contains(&Capability::Blocker)
+    }
+}
+
+/// Optional features, unknown ones are ignored so either side can be newer
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Capability {
+    /// [ServerToClient::UpdateTodos]
+    Todos,
+    /// [ServerToClient::Event]
+    Events,
+    /// [ServerToClient::Clients]
+    Clients,
+    /// the client blocks windows or websites during work periods, profiles can require one to be alive
+    Blocker,
+    #[serde(other)]
+    Unknown,
+}
+
+impl Capability {
+    /// Capabilities this build understands
+    pub const SUPPORTED: &'static [Capability] = &[
+        Capability::Todos,
+        Capability::Events,
+        Capability::Clients,
+        Capability::Blocker,
+    ];
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// the message couldn't be parsed or isn't allowed where it was sent
+    InvalidMessage,
+    Unauthorized,
+    NoTimer,
+    TimerExists,
+    UnknownProfile,
+    /// the timer's profile doesn't allow it
+    NotAllowed,
+    /// the command doesn't make sense in the timer's current state
+    InvalidState,
+    InvalidArgument,
+    /// the client's protocol version isn't supported
+    IncompatibleVersion,
+    Internal,
+    #[serde(other)]
+    Other,
+}
+
+/// What happened to the timer, snapshots alone can't tell a period running out from a skip
 #[serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TimerEvent {
+    PeriodStarted {
+        period: PeriodType,
+        #[serde_as(as = "Option<DurationSeconds<i64>>")]
+        limit: Option<Duration>,
+    },
+    PeriodEnded { period: PeriodType, reason: PeriodEnd },
+    Paused,
+    Resumed,
+    GoalReached,
+    /// the timer was removed, `finished` is false if it was stopped before the goal was fulfilled
+    SessionStopped { finished: bool },
+    /// the profile requires a blocker and none is alive during a work period
+    BlockerLost,
+    /// a blocker came back after [TimerEvent::BlockerLost]
+    BlockerReturned,
+}
+
+/// Summary of a config reload
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ConfigChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+    /// None if the running timer's profile was left untouched or there is no timer
+    pub timer_profile: Option<TimerProfileChange>,
+}
+
+/// A problem with one of the config files
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ConfigDiagnostic {
+    pub file: String,
+    /// like `pomodoro.work_dur`, None if the problem isn't with a single field
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl Display for ConfigDiagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.field {
+            Some(ref field) => write!(f, "{}: {field}: {}", self.file, self.message),
+            None => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TimerProfileChange {
+    /// the running timer uses the new version of its profile
+    Applied,
+    /// the running timer keeps the old version of its profile until it ends
+    Deferred,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ProfileInfo {
     pub name: String,
-    pub pomodoro: Option<PomodoroSettings>
+    /// the profiles it extends, in the order they're merged
+    #[serde(default)]
+    pub extends: Vec<String>,
+    /// same as `profile.pomodoro`, clients from before `profile` was added only read this
+    pub pomodoro: Option<PomodoroSettings>,
+    /// with the profiles it extends merged in
+    pub profile: Profile,
 }
 
 impl PartialEq for ProfileInfo {
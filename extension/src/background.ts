@@ -1,14 +1,41 @@
 import {ConstantBackoff, WebsocketBuilder} from 'websocket-ts';
 
+// have to match PROTOCOL_VERSION and HEARTBEAT_INTERVAL in common/src/ws_common.rs
+const PROTOCOL_VERSION = 1;
+const HEARTBEAT_INTERVAL_MS = 10_000;
+
+// the webextension api, there are no type definitions for it in this project
+declare const chrome: any;
+
+let heartbeat: number | undefined;
+
 new WebsocketBuilder('ws://127.0.0.1:63086/ws')
     .withBackoff(new ConstantBackoff(3000))
-    .onOpen((_, ev) => console.log(`Connected!`))
+    .onOpen((ws, ev) => {
+        console.log(`Connected!`);
+        // read on every connection so a key changed in the options is used on the next retry
+        chrome.storage.local.get("key", ({key}: { key?: string }) => {
+            ws.send(JSON.stringify({Hello: {protocol_version: PROTOCOL_VERSION, client_kind: "Extension", capabilities: ["Blocker"]}}));
+            // the server won't process anything else until we authenticate
+            if (key) {
+                ws.send(JSON.stringify({Authenticate: {key}}));
+            }
+            heartbeat = setInterval(() => ws.send(JSON.stringify("Heartbeat")), HEARTBEAT_INTERVAL_MS);
+        });
+    })
     .onMessage((_, ev) => processMessage(JSON.parse(ev.data as string)))
-    .onClose((_, ev) => console.log(`Disconnected!`))
+    .onClose((_, ev) => {
+        console.log(`Disconnected!`);
+        clearInterval(heartbeat);
+    })
     .build();
 
 type Message = {
     Multiple : Message[],
+} | {
+    Hello: { protocol_version: number, capabilities: string[] }
+} | {
+    Error: { id: number | null, kind: string, message: string }
 } | {
     UpdateTimer: Timer | null
 } | {
@@ -17,25 +44,89 @@ type Message = {
 
 type Timer = {
     profile: {
-        blocking: {
-            websites: string[],
-            hide_web_video: boolean,
-        }
+        blocking: Blocking,
     },
 
     state: TimerState,
 };
 
+type Blocking = {
+    websites: string[],
+    hide_web_video: boolean,
+    // in allowlist mode the websites are the only ones that aren't blocked
+    mode: "Denylist" | "Allowlist",
+};
+
 type TimerState = {
+    progress: "Uninit" | { Running: object } | { Paused: object },
+    period: "Uninit" | "Starting" | "Work" | "ShortBreak" | "LongBreak",
+}
+
+// pages of the browser and the local server are never blocked in allowlist mode
+const ALWAYS_ALLOWED_PROTOCOLS = ["about:", "chrome:", "moz-extension:", "chrome-extension:"];
+const ALWAYS_ALLOWED_HOSTS = ["localhost", "127.0.0.1"];
+
+let timer: Timer | null = null;
 
+let isWebsiteBlocked = (blocking: Blocking, url: URL): boolean => {
+    let listed = blocking.websites.some(site => url.hostname === site || url.hostname.endsWith(`.${site}`));
+    if (blocking.mode === "Allowlist") {
+        return !listed && !ALWAYS_ALLOWED_PROTOCOLS.includes(url.protocol) && !ALWAYS_ALLOWED_HOSTS.includes(url.hostname);
+    }
+    return listed;
 }
 
+// same as TimerState::should_block in common/src/timer.rs
+let shouldBlock = (state: TimerState): boolean =>
+    state.period === "Work" && typeof state.progress === "object" && "Running" in state.progress;
+
+let blockTabIfNeeded = (tabId: number, url: string) => {
+    if (!timer || !shouldBlock(timer.state)) {
+        return;
+    }
+
+    let parsed: URL;
+    try {
+        parsed = new URL(url);
+    } catch {
+        return;
+    }
+    if (isWebsiteBlocked(timer.profile.blocking, parsed)) {
+        console.log(`Blocked ${parsed.hostname}`);
+        chrome.tabs.update(tabId, {url: "about:blank"});
+    }
+}
+
+// tabs that were opened before work started are checked once it does
+let blockOpenTabs = () => {
+    chrome.tabs.query({}, (tabs: { id?: number, url?: string }[]) => {
+        for (let tab of tabs) {
+            if (tab.id !== undefined && tab.url) {
+                blockTabIfNeeded(tab.id, tab.url);
+            }
+        }
+    });
+}
+
+chrome.webNavigation.onBeforeNavigate.addListener((details: { tabId: number, frameId: number, url: string }) => {
+    // only whole pages are blocked, not frames inside them
+    if (details.frameId === 0) {
+        blockTabIfNeeded(details.tabId, details.url);
+    }
+});
+
 let processMessage = (msg: Message) => {
     if ("Multiple" in msg) {
         msg.Multiple.forEach(processMessage);
+    } else if ("Error" in msg) {
+        console.error(`Server error (${msg.Error.kind}): ${msg.Error.message}`);
     } else if ("UpdateTimer" in msg) {
-
+        timer = msg.UpdateTimer;
+        blockOpenTabs();
     } else if ("UpdateTimerState" in msg) {
-
+        if (timer) {
+            timer.state = msg.UpdateTimerState;
+            blockOpenTabs();
+        }
     }
 }
\ No newline at end of file
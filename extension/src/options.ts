@@ -0,0 +1,15 @@
+// the webextension api, there are no type definitions for it in this project
+declare const chrome: any;
+
+let input = document.getElementById("key") as HTMLInputElement;
+
+chrome.storage.local.get("key", ({key}: { key?: string }) => input.value = key ?? "");
+
+document.getElementById("save")!.addEventListener("click", () => {
+    let key = input.value.trim();
+    if (key) {
+        chrome.storage.local.set({key});
+    } else {
+        chrome.storage.local.remove("key");
+    }
+});
@@ -0,0 +1,139 @@
+use crate::timer_logic::TimerCommand;
+use crate::{timer_logic, SState};
+use chrono::Utc;
+use common::ws_common::{Capability, ClientInfo, ClientKind, ServerToClient};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{error, info, instrument};
+
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Clients that finished the handshake
+#[derive(Default)]
+pub struct Clients {
+    next_id: AtomicU64,
+    clients: Mutex<Vec<ClientInfo>>,
+}
+
+impl Clients {
+    pub fn list(&self) -> Vec<ClientInfo> {
+        self.clients.lock().unwrap().clone()
+    }
+
+    pub fn count(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+
+    fn blocker_alive(&self) -> bool {
+        self.clients.lock().unwrap().iter().any(|client| client.is_blocker() && client.alive)
+    }
+
+    /// Marks clients that stopped sending heartbeats, returns whether any changed
+    fn mark_timed_out(&self) -> bool {
+        let now = Utc::now();
+        let mut changed = false;
+        for client in self.clients.lock().unwrap().iter_mut() {
+            if client.alive && !client.is_alive(now) {
+                info!("Client {} stopped sending heartbeats", client.id);
+                client.alive = false;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// Adds the client to the registry until the returned guard is dropped
+pub fn register(
+    state: &SState,
+    kind: ClientKind,
+    version: Option<String>,
+    protocol_version: u32,
+    capabilities: Vec<Capability>,
+) -> Registration {
+    let now = Utc::now();
+    let info = ClientInfo {
+        id: state.clients.next_id.fetch_add(1, Ordering::Relaxed),
+        kind,
+        version,
+        protocol_version,
+        capabilities,
+        connected_at: now,
+        last_heartbeat: now,
+        alive: true,
+    };
+    let id = info.id;
+    info!("{kind:?} client {id} registered");
+
+    state.clients.clients.lock().unwrap().push(info);
+    broadcast(state);
+    Registration { state: state.clone(), id }
+}
+
+pub struct Registration {
+    state: SState,
+    id: u64,
+}
+
+impl Registration {
+    /// Clients are only told when this brings the client back, they don't need every heartbeat
+    pub fn heartbeat(&self) {
+        let mut clients = self.state.clients.clients.lock().unwrap();
+        let Some(client) = clients.iter_mut().find(|client| client.id == self.id) else { return };
+        client.last_heartbeat = Utc::now();
+        let returned = !client.alive;
+        client.alive = true;
+        drop(clients);
+
+        if returned {
+            info!("Client {} is sending heartbeats again", self.id);
+            broadcast(&self.state);
+        }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.state.clients.clients.lock().unwrap().retain(|client| client.id != self.id);
+        info!("Client {} unregistered", self.id);
+        broadcast(&self.state);
+    }
+}
+
+fn broadcast(state: &SState) {
+    if let Err(e) = state.ws_tx.send(ServerToClient::Clients(state.clients.list())) {
+        error!("Failed to send clients: {e}");
+    }
+}
+
+/// Marks clients without heartbeats as dead and tells the timer when a work period that requires a blocker has none alive, and when one comes back
+#[instrument(name = "client watchdog", skip_all)]
+pub async fn watchdog(state: SState) {
+    let mut interval = tokio::time::interval(WATCHDOG_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        if state.clients.mark_timed_out() {
+            broadcast(&state);
+        }
+
+        let command = {
+            let timer = state.timer.borrow();
+            let Some(timer) = timer.as_ref() else { continue };
+            let alive = state.clients.blocker_alive();
+
+            if !timer.state.blocker_lost && timer.profile.requires_blocker && timer.state.should_block() && !alive {
+                TimerCommand::BlockerLost
+            } else if timer.state.blocker_lost && alive {
+                TimerCommand::BlockerReturned
+            } else {
+                continue;
+            }
+        };
+
+        if let Err(e) = timer_logic::send(&state, command).await {
+            error!("Failed to update the timer: {e}");
+        }
+    }
+}
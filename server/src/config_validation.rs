@@ -0,0 +1,67 @@
+use crate::server_config::ServerConfig;
+use chrono::Duration;
+use common::profile::Profile;
+use pathpatterns::Pattern;
+use regex::Regex;
+
+/// A field and what's wrong with it
+pub type Problem = (String, String);
+
+/// Checks what parsing alone can't, so clients never get a profile they fail on
+pub fn validate_profile(profile: &Profile) -> Vec<Problem> {
+    let mut problems = vec![];
+
+    // the lists can be merged from parent profiles, so the value is reported instead of its index
+    for window_name in profile.blocking.window_names.iter() {
+        if let Err(e) = Regex::new(window_name) {
+            problems.push(("blocking.window_names".to_string(), format!("{window_name:?} isn't a valid regex: {e}")));
+        }
+    }
+    for process_path in profile.blocking.process_path.iter() {
+        if Pattern::path(process_path).is_err() {
+            problems.push(("blocking.process_path".to_string(), format!("{process_path:?} isn't a valid path pattern")));
+        }
+    }
+
+    if let Some(ref pomodoro) = profile.pomodoro {
+        positive(&mut problems, "pomodoro.work_dur", pomodoro.work_dur);
+        positive(&mut problems, "pomodoro.short_break_dur", pomodoro.short_break_dur);
+        positive(&mut problems, "pomodoro.long_break_dur", pomodoro.long_break_dur);
+        if pomodoro.small_breaks_before_big_one == 0 {
+            problems.push(("pomodoro.small_breaks_before_big_one".to_string(), "has to be at least 1".to_string()));
+        }
+    }
+    if let Some(delay) = profile.emergency_unlock_delay {
+        not_negative(&mut problems, "emergency_unlock_delay", delay);
+    }
+
+    problems
+}
+
+/// Profiles are validated on their own
+pub fn validate_config(conf: &ServerConfig) -> Vec<Problem> {
+    let mut problems = vec![];
+
+    for (i, hook) in conf.hooks.iter().enumerate() {
+        positive(&mut problems, &format!("hooks[{i}].timeout"), hook.timeout);
+    }
+    for (i, entry) in conf.schedule.iter().enumerate() {
+        if let Some(start_in) = entry.start_in {
+            not_negative(&mut problems, &format!("schedule[{i}].start_in"), start_in);
+        }
+    }
+
+    problems
+}
+
+fn positive(problems: &mut Vec<Problem>, field: &str, dur: Duration) {
+    if dur <= Duration::zero() {
+        problems.push((field.to_string(), "has to be positive".to_string()));
+    }
+}
+
+fn not_negative(problems: &mut Vec<Problem>, field: &str, dur: Duration) {
+    if dur < Duration::zero() {
+        problems.push((field.to_string(), "can't be negative".to_string()));
+    }
+}
@@ -0,0 +1,45 @@
+use common::ws_common::{ErrorKind, ServerToClient};
+use std::fmt::{Display, Formatter};
+
+/// An error that tells the client what went wrong, anything else is reported as [ErrorKind::Internal]
+#[derive(Debug)]
+pub struct CommandError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl CommandError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self { kind, message: message.into() }
+    }
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Like [anyhow::bail] but with an [ErrorKind] for the client
+macro_rules! fail {
+    ($kind:ident, $($arg:tt)+) => {
+        return Err($crate::error::CommandError::new(common::ws_common::ErrorKind::$kind, format!($($arg)+)).into())
+    };
+}
+pub(crate) use fail;
+
+pub fn error_kind(e: &anyhow::Error) -> ErrorKind {
+    if let Some(e) = e.downcast_ref::<CommandError>() {
+        e.kind
+    } else if e.is::<serde_json::Error>() {
+        ErrorKind::InvalidMessage
+    } else {
+        ErrorKind::Internal
+    }
+}
+
+pub fn error_reply(id: Option<u64>, e: &anyhow::Error) -> ServerToClient {
+    ServerToClient::Error { id, kind: error_kind(e), message: e.to_string() }
+}
@@ -0,0 +1,94 @@
+use crate::writer;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use common::get_state_path;
+use common::history::{HistoryEntry, PeriodEnd};
+use common::timer::{PeriodProgress, PeriodType, Timer};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use tracing::{error, warn};
+
+fn history_path() -> PathBuf {
+    get_state_path().join("history.jsonl")
+}
+
+/// Appends an entry for the current period of the timer. Nothing is recorded if no period has started yet
+pub fn record(timer: &Timer, ended: PeriodEnd, time: DateTime<Utc>) {
+    if let Some(entry) = entry(timer, ended, time) {
+        writer::queue(writer::Write::History(entry));
+    }
+}
+
+/// The entry for the current period of the timer, None if no period has started yet
+pub fn entry(timer: &Timer, ended: PeriodEnd, time: DateTime<Utc>) -> Option<HistoryEntry> {
+    if timer.state.period == PeriodType::Uninit || matches!(timer.state.progress, PeriodProgress::Uninit) {
+        return None;
+    }
+
+    Some(HistoryEntry {
+        time,
+        profile: timer.profile.name.clone(),
+        goal: timer.goal.clone(),
+        period: timer.state.period,
+        planned: timer.state.progress.limit(),
+        actual: timer.state.progress.elapsed_at(time),
+        ended,
+    })
+}
+
+/// Appends the entry right away, use [record] from the timer actor
+pub fn write(entry: &HistoryEntry) {
+    if let Err(e) = append(entry) {
+        error!("Failed to record history: {e}");
+    }
+}
+
+fn append(entry: &HistoryEntry) -> Result<()> {
+    fs::create_dir_all(get_state_path())?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(history_path())?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+
+    Ok(())
+}
+
+/// Reads the entries between `from` and `to` (inclusive), optionally only the ones of a profile
+pub fn query(
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    profile: Option<&str>,
+) -> Result<Vec<HistoryEntry>> {
+    let path = history_path();
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut entries = vec![];
+    for line in fs::read_to_string(path)?.lines() {
+        if line.is_empty() {
+            continue;
+        }
+
+        // a broken line shouldn't make the rest of the history unreadable
+        let entry = match serde_json::from_str::<HistoryEntry>(line) {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("Skipping unreadable history entry: {e}");
+                continue;
+            }
+        };
+
+        if from.is_none_or(|from| entry.time >= from)
+            && to.is_none_or(|to| entry.time <= to)
+            && profile.is_none_or(|profile| entry.profile == profile)
+        {
+            entries.push(entry);
+        }
+    }
+
+    Ok(entries)
+}
@@ -0,0 +1,151 @@
+use crate::SState;
+use chrono::Duration;
+use common::duration::HumanDuration;
+use common::timer::{PeriodType, Timer, TimerGoal};
+use common::ws_common::TimerEvent;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use std::process::Stdio;
+use std::sync::OnceLock;
+use tokio::process::Command;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tracing::{error, info, instrument, warn};
+
+#[serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Hook {
+    pub on: Vec<HookEvent>,
+    /// run with `sh -c`
+    pub command: String,
+    /// the command is killed if it runs longer than this
+    #[serde(default = "default_timeout")]
+    #[serde_as(as = "HumanDuration")]
+    pub timeout: Duration,
+}
+
+fn default_timeout() -> Duration {
+    Duration::seconds(10)
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum HookEvent {
+    WorkStarted,
+    BreakStarted,
+    Paused,
+    Resumed,
+    SessionEnded,
+}
+
+impl HookEvent {
+    fn from_timer_event(event: &TimerEvent) -> Option<Self> {
+        Some(match event {
+            TimerEvent::PeriodStarted { period: PeriodType::Work, .. } => HookEvent::WorkStarted,
+            TimerEvent::PeriodStarted { period: PeriodType::ShortBreak | PeriodType::LongBreak, .. } => HookEvent::BreakStarted,
+            TimerEvent::Paused => HookEvent::Paused,
+            TimerEvent::Resumed => HookEvent::Resumed,
+            TimerEvent::SessionStopped { .. } => HookEvent::SessionEnded,
+            _ => return None,
+        })
+    }
+}
+
+type Env = Vec<(&'static str, String)>;
+
+static QUEUE: OnceLock<UnboundedSender<(HookEvent, Env)>> = OnceLock::new();
+
+/// Runs the `[[hooks]]` of the config that match the events. `timer` is the timer the events happened to,
+/// for a session that ended that's the timer before it was removed.
+/// Hooks run one at a time in the order of their events, even across calls, so a slow hook delays the ones after it
+/// by at most its timeout
+pub fn run(state: &SState, events: &[TimerEvent], timer: Option<&Timer>) {
+    let Some(timer) = timer else { return };
+
+    let queue = QUEUE.get_or_init(|| {
+        let (tx, mut rx) = unbounded_channel::<(HookEvent, Env)>();
+        let state = state.clone();
+        tokio::spawn(async move {
+            while let Some((hook_event, env)) = rx.recv().await {
+                let hooks = state.conf.read().await.hooks.clone();
+                for hook in hooks.into_iter().filter(|hook| hook.on.contains(&hook_event)) {
+                    run_hook(hook, env.clone()).await;
+                }
+            }
+        });
+        tx
+    });
+
+    for event in events {
+        let Some(hook_event) = HookEvent::from_timer_event(event) else { continue };
+        if queue.send((hook_event, hook_env(hook_event, event, timer))).is_err() {
+            error!("Hook queue isn't running");
+        }
+    }
+}
+
+/// Environment variables describing the event and the timer
+fn hook_env(hook_event: HookEvent, event: &TimerEvent, timer: &Timer) -> Env {
+    let mut env = vec![
+        ("WATCHWAH_EVENT", format!("{hook_event:?}")),
+        ("WATCHWAH_PROFILE", timer.profile.name.clone()),
+        ("WATCHWAH_PERIOD", format!("{:?}", timer.state.period)),
+        ("WATCHWAH_POMODOROS_DONE", timer.state.pomodoros_done.to_string()),
+    ];
+    if let Some(limit) = timer.state.progress.limit() {
+        let left = limit - timer.state.progress.elapsed();
+        env.push(("WATCHWAH_PERIOD_LEFT", left.num_seconds().to_string()));
+    }
+    if let TimerGoal::Time(goal) = timer.goal {
+        let left = (goal - timer.state.dur_worked()).max(Duration::zero());
+        env.push(("WATCHWAH_GOAL_LEFT", left.num_seconds().to_string()));
+    }
+    if let TimerEvent::SessionStopped { finished } = event {
+        env.push(("WATCHWAH_FINISHED", finished.to_string()));
+    }
+    env
+}
+
+#[instrument(name = "hook", skip_all, fields(command = hook.command))]
+async fn run_hook(hook: Hook, env: Env) {
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(&hook.command)
+        .envs(env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+    let child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            error!("Failed to start: {e}");
+            return;
+        }
+    };
+
+    // dropping the future on timeout kills the command
+    let timeout = hook.timeout.to_std().unwrap_or_default();
+    let output = match tokio::time::timeout(timeout, child.wait_with_output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            error!("Failed to wait for the command: {e}");
+            return;
+        }
+        Err(_) => {
+            warn!("Killed after {}s", hook.timeout.num_seconds());
+            return;
+        }
+    };
+
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        info!("{line}");
+    }
+    for line in String::from_utf8_lossy(&output.stderr).lines() {
+        warn!("{line}");
+    }
+    if output.status.success() {
+        info!("Finished");
+    } else {
+        warn!("Failed with {}", output.status);
+    }
+}
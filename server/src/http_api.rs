@@ -0,0 +1,110 @@
+use crate::error::{error_kind, CommandError};
+use crate::server_config::profile_infos;
+use crate::server_ws::keys_match;
+use crate::timer_logic::TimerCommand;
+use crate::{timer_logic, SState};
+use axum::extract::rejection::JsonRejection;
+use axum::extract::State;
+use axum::http::header::AUTHORIZATION;
+use axum::http::{Request, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::Duration;
+use common::timer::{Timer, TimerGoal};
+use common::ws_common::{ErrorKind, ProfileInfo};
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use serde_with::DurationSeconds;
+use std::ops::Deref;
+
+/// Routes for scripts and other programs that don't want to keep a websocket open.
+/// Commands go through the timer actor so websocket clients get the same updates
+pub fn router(state: SState) -> Router {
+    Router::new()
+        .route("/timer", get(get_timer).post(create_timer))
+        .route("/timer/pause", post(|state| run(state, TimerCommand::Pause)))
+        .route("/timer/unpause", post(|state| run(state, TimerCommand::Unpause)))
+        .route("/timer/skip", post(|state| run(state, TimerCommand::Skip)))
+        .route("/timer/stop", post(|state| run(state, TimerCommand::Stop)))
+        .route("/profiles", get(get_profiles))
+        .route_layer(middleware::from_fn_with_state(state.clone(), check_key))
+        .with_state(state)
+}
+
+#[serde_as]
+#[derive(Deserialize)]
+struct CreateTimer {
+    #[serde(default)]
+    goal: TimerGoal,
+    profile_name: String,
+    #[serde_as(as = "Option<DurationSeconds<i64>>")]
+    #[serde(default)]
+    start_in: Option<Duration>,
+}
+
+async fn get_timer(State(state): State<SState>) -> Json<Option<Timer>> {
+    Json(state.timer.borrow().clone())
+}
+
+async fn get_profiles(State(state): State<SState>) -> Json<Vec<ProfileInfo>> {
+    Json(profile_infos(state.conf.read().await.deref()))
+}
+
+async fn create_timer(
+    state: State<SState>,
+    body: Result<Json<CreateTimer>, JsonRejection>,
+) -> Result<Json<Option<Timer>>, ApiError> {
+    let Json(CreateTimer { goal, profile_name, start_in }) =
+        body.map_err(|e| CommandError::new(ErrorKind::InvalidMessage, e.body_text()))?;
+    run(state, TimerCommand::Create { goal, profile_name, start_in }).await
+}
+
+/// Returns the timer after the command was applied
+async fn run(State(state): State<SState>, command: TimerCommand) -> Result<Json<Option<Timer>>, ApiError> {
+    timer_logic::send(&state, command).await?;
+    Ok(Json(state.timer.borrow().clone()))
+}
+
+/// Requires `Authorization: Bearer <key>` if the server has a key set
+pub async fn check_key<B>(State(state): State<SState>, request: Request<B>, next: Next<B>) -> Result<Response, ApiError> {
+    if let Some(key) = state.conf.read().await.key.clone() {
+        let provided = request.headers().get(AUTHORIZATION)
+            .and_then(|header| header.to_str().ok())
+            .and_then(|header| header.strip_prefix("Bearer "));
+        if !provided.is_some_and(|provided| keys_match(&key, provided)) {
+            return Err(CommandError::new(ErrorKind::Unauthorized, "Missing or wrong key").into());
+        }
+    }
+    Ok(next.run(request).await)
+}
+
+pub struct ApiError(anyhow::Error);
+
+impl<E: Into<anyhow::Error>> From<E> for ApiError {
+    fn from(e: E) -> Self {
+        Self(e.into())
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    kind: ErrorKind,
+    message: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let kind = error_kind(&self.0);
+        let status = match kind {
+            ErrorKind::InvalidMessage | ErrorKind::InvalidArgument | ErrorKind::IncompatibleVersion => StatusCode::BAD_REQUEST,
+            ErrorKind::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorKind::NotAllowed => StatusCode::FORBIDDEN,
+            ErrorKind::NoTimer | ErrorKind::UnknownProfile => StatusCode::NOT_FOUND,
+            ErrorKind::TimerExists | ErrorKind::InvalidState => StatusCode::CONFLICT,
+            ErrorKind::Internal | ErrorKind::Other => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(ErrorBody { kind, message: self.0.to_string() })).into_response()
+    }
+}
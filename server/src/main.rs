@@ -1,20 +1,36 @@
+mod clients;
+mod config_validation;
+mod error;
+mod history;
+mod hooks;
+mod http_api;
+mod metrics;
+mod scheduler;
 mod server_config;
+mod server_uds;
 mod server_ws;
 mod timer_logic;
+mod timer_persistence;
+mod writer;
 
+use crate::clients::Clients;
+use crate::metrics::Metrics;
 use crate::server_config::ServerConfig;
+use crate::server_ws::Broadcast;
+use crate::timer_logic::TimerRequest;
 use axum::extract::{ConnectInfo, WebSocketUpgrade};
 use axum::routing::get;
-use axum::Router;
-use common::register_tracing;
+use axum::{middleware, Router};
+use common::{get_discovery_path, get_override, get_runtime_path, register_tracing};
 use common::timer::Timer;
 use common::ws_common::ServerToClient;
+use std::fs;
 use std::net::SocketAddr;
 use std::process;
 use std::sync::Arc;
-use tokio::sync::mpsc::UnboundedSender;
-use tokio::sync::{broadcast, Mutex, Notify, RwLock};
-use tracing::error;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::{broadcast, watch, RwLock};
+use tracing::{error, info, warn};
 
 pub type SState = Arc<State>;
 pub struct State {
@@ -22,33 +38,60 @@ pub struct State {
 
     pub conf: RwLock<ServerConfig>,
 
-    pub timer: Mutex<Option<Timer>>,
-    pub cancel_timer_task: Arc<Notify>,
+    /// commands for [timer_logic::timer_actor]
+    pub timer_tx: UnboundedSender<TimerRequest>,
+    /// the timer as last published by the timer actor
+    pub timer: watch::Receiver<Option<Timer>>,
+
+    pub metrics: Metrics,
+    pub clients: Clients,
 }
 
 // todo: tracing
 
 #[tokio::main]
 pub async fn main() {
-    register_tracing("127.0.0.1:6669");
+    // loaded before tracing is registered because it has the console address
+    let conf = server_config::load_config();
+    let console_address = get_override("console-address", "WATCHWAH_CONSOLE_ADDRESS")
+        .or_else(|| conf.as_ref().ok().map(|conf| conf.console_address))
+        .unwrap_or_else(server_config::default_console_address);
+    register_tracing(console_address);
 
     // state
-    let (ws_tx, _ws_rx) = broadcast::channel::<String>(16);
+    let (ws_tx, _ws_rx) = broadcast::channel::<Broadcast>(16);
+    let (timer_tx, timer_rx) = unbounded_channel();
+    let (timer_watch_tx, timer_watch_rx) = watch::channel(None);
 
     let state = Arc::new(State {
         ws_tx: server_ws::serialize_incoming(ws_tx.clone()),
 
-        conf: match server_config::load_config() {
+        conf: match conf {
             Ok(conf) => RwLock::new(conf),
             Err(err) => {
                 error!("Unable to load config: {err}");
                 process::exit(-1)
             }
         },
-        timer: Mutex::new(None),
-        cancel_timer_task: Arc::new(Notify::new()),
+        timer_tx,
+        timer: timer_watch_rx,
+        metrics: Metrics::default(),
+        clients: Clients::default(),
     });
 
+    if state.conf.read().await.key.is_none() {
+        warn!("No key set in config.toml, anyone that can reach the server can control the timer");
+    }
+
+    // timer
+    tokio::spawn(timer_logic::timer_actor(state.clone(), timer_rx, timer_watch_tx));
+
+    // scheduled timers
+    tokio::spawn(scheduler::scheduler(state.clone()));
+
+    // blockers required by profiles
+    tokio::spawn(clients::watchdog(state.clone()));
+
     // config monitor
     let monitor = server_config::config_monitor(state.clone());
     tokio::spawn(async {
@@ -57,8 +100,24 @@ pub async fn main() {
         }
     });
 
+    // unix socket
+    let uds = server_uds::listen(state.clone(), ws_tx.clone());
+    tokio::spawn(async {
+        if let Err(e) = uds.await {
+            error!("Unix socket failed: {e}");
+        }
+    });
+
     // axum
+    let address = get_override("address", "WATCHWAH_ADDRESS").unwrap_or(state.conf.read().await.address);
+    let api = http_api::router(state.clone());
+    let metrics = Router::new()
+        .route("/metrics", get(metrics::metrics))
+        .route_layer(middleware::from_fn_with_state(state.clone(), http_api::check_key))
+        .with_state(state.clone());
     let router = Router::new()
+        .nest("/api", api)
+        .merge(metrics)
         .route(
             "/ws",
             get(
@@ -70,8 +129,20 @@ pub async fn main() {
             ),
         );
 
-    let server = axum::Server::bind(&"127.0.0.1:63086".parse().unwrap())
-        .serve(router.into_make_service_with_connect_info::<SocketAddr>());
+    let server = match axum::Server::try_bind(&address) {
+        Ok(server) => server.serve(router.into_make_service_with_connect_info::<SocketAddr>()),
+        Err(e) => {
+            error!("Unable to listen on {address}: {e}");
+            process::exit(-1)
+        }
+    };
+
+    // the port might have been picked by the os
+    let address = server.local_addr();
+    info!("Listening on {address}");
+    if let Err(e) = fs::create_dir_all(get_runtime_path()).and_then(|_| fs::write(get_discovery_path(), address.to_string())) {
+        error!("Failed to write the discovery file: {e}");
+    }
 
     if let Err(e) = server.await {
         error!("[Server] Axum failed with {e}")
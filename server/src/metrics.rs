@@ -0,0 +1,81 @@
+use crate::SState;
+use axum::extract::State;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::IntoResponse;
+use common::timer::PeriodType;
+use common::ws_common::TimerEvent;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counters that can't be computed from the timer, everything else is read when scraped
+#[derive(Default)]
+pub struct Metrics {
+    pub sessions_started: AtomicU64,
+    pub sessions_finished: AtomicU64,
+    pub sessions_stopped: AtomicU64,
+    pub config_reloads: AtomicU64,
+    pub config_reload_failures: AtomicU64,
+}
+
+impl Metrics {
+    pub fn record_events(&self, events: &[TimerEvent]) {
+        for event in events {
+            match event {
+                TimerEvent::SessionStopped { finished: true } => self.sessions_finished.fetch_add(1, Ordering::Relaxed),
+                TimerEvent::SessionStopped { finished: false } => self.sessions_stopped.fetch_add(1, Ordering::Relaxed),
+                _ => continue,
+            };
+        }
+    }
+}
+
+/// Prometheus text format
+pub async fn metrics(State(state): State<SState>) -> impl IntoResponse {
+    let mut out = String::new();
+    let metrics = &state.metrics;
+    let timer = state.timer.borrow().clone();
+
+    // writing to a String can't fail
+    let mut metric = |name: &str, help: &str, kind: &str, values: &[(&str, f64)]| {
+        writeln!(out, "# HELP {name} {help}").ok();
+        writeln!(out, "# TYPE {name} {kind}").ok();
+        for (labels, value) in values {
+            writeln!(out, "{name}{labels} {value}").ok();
+        }
+    };
+
+    let period = timer.as_ref().map(|timer| timer.state.period);
+    let periods = [PeriodType::Starting, PeriodType::Work, PeriodType::ShortBreak, PeriodType::LongBreak]
+        .map(|p| (format!("{{period=\"{p:?}\"}}"), if period == Some(p) { 1. } else { 0. }));
+    let periods: Vec<_> = periods.iter().map(|(labels, value)| (labels.as_str(), *value)).collect();
+    metric("watchwah_period", "1 for the type of the current period", "gauge", &periods);
+
+    let running = timer.as_ref().is_some_and(|timer| timer.state.progress.is_running());
+    metric("watchwah_timer_running", "1 if a timer exists and isn't paused", "gauge", &[("", if running { 1. } else { 0. })]);
+
+    if let Some(ref timer) = timer {
+        let progress = &timer.state.progress;
+        let elapsed = progress.elapsed();
+        metric("watchwah_period_elapsed_seconds", "Time spent in the current period", "gauge", &[("", seconds(elapsed))]);
+        if let Some(limit) = progress.limit() {
+            metric("watchwah_period_remaining_seconds", "Time left in the current period", "gauge", &[("", seconds(limit - elapsed))]);
+        }
+        metric("watchwah_total_dur_worked_seconds", "Work time of the session, including the planned rest of the current work period", "gauge", &[("", seconds(timer.state.total_dur_worked))]);
+    }
+
+    let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed) as f64;
+    metric("watchwah_sessions_started_total", "Timers created", "counter", &[("", load(&metrics.sessions_started))]);
+    metric("watchwah_sessions_finished_total", "Timers that ended with their goal fulfilled", "counter", &[("", load(&metrics.sessions_finished))]);
+    metric("watchwah_sessions_stopped_total", "Timers stopped before their goal was fulfilled", "counter", &[("", load(&metrics.sessions_stopped))]);
+    metric("watchwah_connected_clients", "Websocket and unix socket clients that finished the handshake", "gauge", &[("", state.clients.count() as f64)]);
+    metric("watchwah_config_reloads_total", "Config reloads", "counter", &[
+        ("{result=\"success\"}", load(&metrics.config_reloads)),
+        ("{result=\"failure\"}", load(&metrics.config_reload_failures)),
+    ]);
+
+    ([(CONTENT_TYPE, "text/plain; version=0.0.4")], out)
+}
+
+fn seconds(dur: chrono::Duration) -> f64 {
+    dur.num_milliseconds() as f64 / 1000.
+}
@@ -0,0 +1,89 @@
+use crate::timer_logic::TimerCommand;
+use crate::{timer_logic, SState};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveTime, TimeZone, Weekday};
+use common::duration::HumanDuration;
+use common::timer::TimerGoal;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_with::serde_as;
+use tracing::{error, info, instrument};
+
+/// Occurrences older than this are considered missed, for example because the computer was suspended
+const MAX_DELAY_MINUTES: i64 = 5;
+
+#[serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScheduleEntry {
+    /// every day if empty
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    /// local time, "HH:MM" or "HH:MM:SS"
+    #[serde(deserialize_with = "deserialize_time")]
+    pub time: NaiveTime,
+    pub profile: String,
+    #[serde(default)]
+    pub goal: TimerGoal,
+    #[serde(default)]
+    #[serde_as(as = "Option<HumanDuration>")]
+    pub start_in: Option<Duration>,
+}
+
+impl ScheduleEntry {
+    /// whether the entry has an occurrence in (after, until]
+    fn is_due(&self, after: DateTime<Local>, until: DateTime<Local>) -> bool {
+        // the range can span midnight
+        [after.date_naive(), until.date_naive()].into_iter().any(|date| {
+            if !self.days.is_empty() && !self.days.contains(&date.weekday()) {
+                return false;
+            }
+            match Local.from_local_datetime(&date.and_time(self.time)).earliest() {
+                Some(occurrence) => after < occurrence && occurrence <= until,
+                None => false, // skipped by a DST change
+            }
+        })
+    }
+}
+
+fn deserialize_time<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveTime, D::Error> {
+    let str = String::deserialize(deserializer)?;
+    NaiveTime::parse_from_str(&str, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(&str, "%H:%M:%S"))
+        .map_err(serde::de::Error::custom)
+}
+
+/// Creates timers for the `[[schedule]]` entries of the config. The entries are read on every check so config reloads
+/// are picked up automatically
+#[instrument(name = "scheduler", skip_all)]
+pub async fn scheduler(state: SState) {
+    let mut last_check = Local::now();
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(10));
+
+    loop {
+        interval.tick().await;
+
+        let now = Local::now();
+        let after = last_check.max(now - Duration::minutes(MAX_DELAY_MINUTES));
+        last_check = now;
+
+        let due = state.conf.read().await.schedule.iter()
+            .filter(|entry| entry.is_due(after, now))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for entry in due {
+            if state.timer.borrow().is_some() {
+                info!("Skipping scheduled timer for {}, a timer is already running", entry.profile);
+                continue;
+            }
+
+            let command = TimerCommand::Create {
+                goal: entry.goal,
+                profile_name: entry.profile.clone(),
+                start_in: entry.start_in,
+            };
+            match timer_logic::send(&state, command).await {
+                Ok(()) => info!("Started scheduled timer for {}", entry.profile),
+                Err(e) => error!("Failed to start scheduled timer for {}: {e}", entry.profile),
+            }
+        }
+    }
+}
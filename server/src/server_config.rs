@@ -1,24 +1,58 @@
+use crate::hooks::Hook;
+use crate::scheduler::ScheduleEntry;
+use crate::timer_logic::TimerCommand;
 use crate::{timer_logic, SState};
-use anyhow::{anyhow, bail, Result};
+use crate::config_validation::{validate_config, validate_profile};
+use crate::error::{fail, CommandError};
+use anyhow::{anyhow, Result};
 use common::get_config_path;
-use common::profile::Profile;
+use common::profile::{BlockingMode, Profile};
 use notify::event::{CreateKind, RemoveKind};
 use notify::{EventKind, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
 use std::fs;
+use std::net::SocketAddr;
 use std::ops::Deref;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
 use tokio::sync::mpsc::unbounded_channel;
+use toml::{Table, Value};
 use tracing::{error, info, instrument};
-use common::ws_common::{ProfileInfo, ServerToClient};
+use common::ws_common::{ConfigChanges, ConfigDiagnostic, ErrorKind, ProfileInfo, ServerToClient, TimerProfileChange};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ServerConfig {
-    // todo: key
+    /// clients have to authenticate with this key before doing anything, anyone can connect if it's not set
+    #[serde(default)]
+    pub key: Option<String>,
+    /// where the websocket and the http api listen, port 0 picks a free one. Only read at startup
+    #[serde(default = "default_address")]
+    pub address: SocketAddr,
+    /// where tokio-console can connect. Only read at startup
+    #[serde(default = "default_console_address")]
+    pub console_address: SocketAddr,
+
+    /// timers that get created automatically
+    #[serde(default)]
+    pub schedule: Vec<ScheduleEntry>,
+    /// commands that run when something happens to the timer
+    #[serde(default)]
+    pub hooks: Vec<Hook>,
+
     #[serde(skip)] // generated from neighboring files
     pub profiles: Vec<Profile>,
 }
 
+pub fn default_address() -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 63086))
+}
+
+pub fn default_console_address() -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 6669))
+}
+
 #[instrument(name = "config monitor", skip_all)]
 pub async fn config_monitor(state: SState) -> Result<()> {
     let (tx, mut rx) = unbounded_channel();
@@ -35,27 +69,42 @@ pub async fn config_monitor(state: SState) -> Result<()> {
         {
             match tokio::task::spawn_blocking(load_config).await.unwrap() {
                 Ok(new_conf) => {
-                    info!("Config updated!");
-                    {
-                        *state.conf.write().await = new_conf;
-                    }
+                    state.metrics.config_reloads.fetch_add(1, Ordering::Relaxed);
+                    let profiles = new_conf.profiles.clone();
+                    let mut changes = {
+                        let mut conf = state.conf.write().await;
+                        let changes = diff_profiles(&conf.profiles, &profiles);
+                        *conf = new_conf;
+                        changes
+                    };
+                    info!("Config updated! {changes:?}");
 
-                    let mut timer = state.timer.lock().await;
-                    if timer.is_some() {
+                    let timer = state.timer.borrow().clone();
+                    if let Some(ref timer) = timer {
+                        changes.timer_profile = timer_logic::profile_change(timer, &profiles);
+                        info!("Timer profile change: {:?}", changes.timer_profile);
+                    }
+                    if let (Some(timer), Some(TimerProfileChange::Applied)) = (timer, changes.timer_profile) {
+                        let profile = profiles.into_iter().find(|p| p.name == timer.profile.name).unwrap();
                         // we don't propagate the error as that would stop the monitor
-                        match timer_logic::stop_timer(&mut *timer, &state) {
-                            Ok(msg) => {
-                                let msg = ServerToClient::Multiple(vec![
-                                    profiles_msg(state.conf.read().await.deref()),
-                                    msg.to_msg(timer.as_ref()).unwrap(),
-                                ]);
-                                state.ws_tx.send(msg).ok();
-                            }
-                            Err(e) => error!("Failed to stop timer: {e}"),
+                        if let Err(e) = timer_logic::send(&state, TimerCommand::RefreshProfile(profile)).await {
+                            error!("Failed to refresh timer profile: {e}");
                         }
                     }
+
+                    let msg = ServerToClient::Multiple(vec![
+                        profiles_msg(state.conf.read().await.deref()),
+                        ServerToClient::RefreshedConfig(changes),
+                    ]);
+                    state.ws_tx.send(msg).ok();
+                }
+                Err(InvalidConfig(diagnostics)) => {
+                    state.metrics.config_reload_failures.fetch_add(1, Ordering::Relaxed);
+                    for diagnostic in diagnostics.iter() {
+                        error!("Invalid config, keeping the last good one: {diagnostic}");
+                    }
+                    state.ws_tx.send(ServerToClient::ConfigError(diagnostics)).ok();
                 }
-                Err(e) => error!("Failed to parse config: {e}"),
             }
         }
     }
@@ -63,46 +112,469 @@ pub async fn config_monitor(state: SState) -> Result<()> {
     Ok(())
 }
 
-pub fn profiles_msg(conf: &ServerConfig) -> ServerToClient {
-    ServerToClient::UpdateProfiles(conf.profiles.iter().map(|p| ProfileInfo{
+fn diff_profiles(old: &[Profile], new: &[Profile]) -> ConfigChanges {
+    let mut changes = ConfigChanges::default();
+    for profile in new {
+        match old.iter().find(|p| p.name == profile.name) {
+            None => changes.added.push(profile.name.clone()),
+            Some(old_profile) if old_profile != profile => changes.changed.push(profile.name.clone()),
+            Some(_) => {}
+        }
+    }
+    changes.removed = old.iter()
+        .filter(|p| !new.iter().any(|n| n.name == p.name))
+        .map(|p| p.name.clone())
+        .collect();
+    changes
+}
+
+pub fn profile_infos(conf: &ServerConfig) -> Vec<ProfileInfo> {
+    conf.profiles.iter().map(|p| ProfileInfo{
         name: p.name.to_string(),
+        extends: p.extends.clone(),
         pomodoro: p.pomodoro.clone(),
-    }).collect())
+        profile: p.clone(),
+    }).collect()
+}
+
+pub fn profiles_msg(conf: &ServerConfig) -> ServerToClient {
+    ServerToClient::UpdateProfiles(profile_infos(conf))
+}
+
+/// Everything that's wrong with the config files, shown one per line
+#[derive(Debug)]
+pub struct InvalidConfig(pub Vec<ConfigDiagnostic>);
+
+impl Display for InvalidConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, diagnostic) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{diagnostic}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InvalidConfig {}
+
+fn diagnostic(file: &Path, field: Option<String>, message: impl Display) -> ConfigDiagnostic {
+    ConfigDiagnostic { file: file.display().to_string(), field, message: message.to_string() }
 }
 
-pub fn load_config() -> Result<ServerConfig> {
+/// Loads and validates config.toml and the profiles. Nothing is returned unless every file is fine
+pub fn load_config() -> Result<ServerConfig, InvalidConfig> {
     let path = get_config_path();
     let config_path = path.join("config.toml");
+    let mut diagnostics = vec![];
 
-    let mut conf: ServerConfig = if config_path.exists() {
-        let contents = fs::read_to_string(config_path)?;
-        toml::from_str(&contents)?
+    let conf = if config_path.exists() {
+        match fs::read_to_string(&config_path).map_err(anyhow::Error::from)
+            .and_then(|contents| Ok(toml::from_str::<ServerConfig>(&contents)?))
+        {
+            Ok(conf) => Some(conf),
+            Err(e) => {
+                diagnostics.push(diagnostic(&config_path, None, e));
+                None
+            }
+        }
     } else {
-        bail!("config.toml missing!");
+        diagnostics.push(diagnostic(&config_path, None, "config.toml missing!"));
+        None
     };
+    if let Some(ref conf) = conf {
+        diagnostics.extend(validate_config(conf).into_iter()
+            .map(|(field, message)| diagnostic(&config_path, Some(field), message)));
+    }
+
+    let profiles = load_profiles(path.join("profiles"), &mut diagnostics);
 
-    conf.profiles = load_profiles(path.join("profiles"))?;
-    Ok(conf)
+    match conf {
+        Some(mut conf) if diagnostics.is_empty() => {
+            conf.profiles = profiles;
+            Ok(conf)
+        }
+        _ => Err(InvalidConfig(diagnostics)),
+    }
 }
 
-fn load_profiles(path: PathBuf) -> Result<Vec<Profile>> {
+/// Writes the profile like a manual edit would, the config monitor reloads it
+pub fn save_profile(name: &str, extends: &[String], profile: &Profile) -> Result<()> {
+    let path = profile_path(name)?;
+    let problems: Vec<_> = validate_profile(profile).into_iter()
+        .map(|(field, message)| format!("{field}: {message}"))
+        .collect();
+    if !problems.is_empty() {
+        fail!(InvalidArgument, "{}", problems.join(", "))
+    }
+
+    let Value::Table(mut table) = Value::try_from(profile)? else { unreachable!() };
+    if !extends.is_empty() {
+        table = own_values(name, &path, extends, table)?;
+        table.insert("extends".to_string(), Value::Array(extends.iter().cloned().map(Value::String).collect()));
+    }
+
+    // write to a temporary file first so the monitor can't read a half written profile, it ignores the extension
+    let tmp_path = path.with_extension("toml.tmp");
+    fs::write(&tmp_path, toml::to_string(&table)?)?;
+    fs::rename(tmp_path, path)?;
+
+    info!("Profile {name} saved");
+    Ok(())
+}
+
+pub fn delete_profile(name: &str) -> Result<()> {
+    let path = profile_path(name)?;
+    if !path.exists() {
+        fail!(UnknownProfile, "Profile {name} not found")
+    }
+
+    // the config couldn't be loaded anymore
+    let dependents: Vec<_> = read_profile_files(&get_config_path().join("profiles"), &mut vec![]).into_iter()
+        .filter(|(_, file)| file.parents.iter().any(|parent| parent == name))
+        .map(|(name, _)| name)
+        .collect();
+    if !dependents.is_empty() {
+        fail!(InvalidState, "Profile {name} is extended by {}", dependents.join(", "))
+    }
+
+    fs::remove_file(path)?;
+
+    info!("Profile {name} deleted");
+    Ok(())
+}
+
+/// Only names that are safe to use as a file name
+fn profile_path(name: &str) -> Result<PathBuf> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ' '));
+    if !valid {
+        fail!(InvalidArgument, "{name:?} can't be used as a profile name")
+    }
+    Ok(get_config_path().join("profiles").join(format!("{name}.toml")))
+}
+
+/// Leaves out what the parents already set the same way, except for the values the file had set itself.
+/// Lists in `blocking` only keep the items the parents don't have, since they're combined, unless the profile
+/// switches `blocking.mode`
+fn own_values(name: &str, path: &Path, extends: &[String], mut table: Table) -> Result<Table> {
+    let mut files = read_profile_files(&get_config_path().join("profiles"), &mut vec![]);
+    let explicit = files.remove(name).map(|file| file.table).unwrap_or_default();
+    // without a table of its own it resolves to what it inherits
+    files.insert(name.to_string(), ProfileFile { path: path.to_path_buf(), parents: extends.to_vec(), table: Table::new() });
+    let inherited = resolve_profile(name, &files, &mut vec![])
+        .map_err(|e| CommandError::new(ErrorKind::InvalidArgument, e.message))?;
+    // parsing fills in the defaults and makes durations comparable
+    let inherited = Value::try_from(Value::Table(inherited).try_into::<Profile>()?)?;
+
+    let mut own = Table::new();
+    for (key, value) in inherited.as_table().unwrap() {
+        if !table.contains_key(key) {
+            fail!(InvalidArgument, "{key} is set by {} and can't be removed", extends.join(", "))
+        }
+        if key != "blocking" {
+            let new_value = table.remove(key).unwrap();
+            if new_value != *value || explicit.contains_key(key) {
+                own.insert(key.clone(), new_value);
+            }
+        }
+    }
+
+    let Some(Value::Table(mut blocking)) = table.remove("blocking") else { unreachable!() };
+    let explicit_blocking = explicit.get("blocking").and_then(Value::as_table);
+    // in another mode than the parents the lists replace theirs instead of adding to them
+    let switches_mode = blocking_mode(&blocking) != blocking_mode(inherited["blocking"].as_table().unwrap());
+    let mut own_blocking = Table::new();
+    for (key, inherited) in inherited["blocking"].as_table().unwrap() {
+        let Some(value) = blocking.remove(key) else { continue };
+        let explicit = explicit_blocking.is_some_and(|t| t.contains_key(key));
+        match (inherited, value) {
+            (Value::Array(_), Value::Array(list)) if switches_mode => {
+                if !list.is_empty() || explicit {
+                    own_blocking.insert(key.clone(), Value::Array(list));
+                }
+            }
+            (Value::Array(inherited), Value::Array(list)) => {
+                if let Some(item) = inherited.iter().find(|item| !list.contains(item)) {
+                    fail!(InvalidArgument, "{item} in blocking.{key} is set by {} and can't be removed", extends.join(", "))
+                }
+                let list: Vec<_> = list.into_iter().filter(|item| !inherited.contains(item)).collect();
+                if !list.is_empty() || explicit {
+                    own_blocking.insert(key.clone(), Value::Array(list));
+                }
+            }
+            (inherited, value) => if *inherited != value || explicit {
+                own_blocking.insert(key.clone(), value);
+            },
+        }
+    }
+    // anything the parents don't have at all
+    own_blocking.extend(blocking);
+    own.extend(table);
+    if !own_blocking.is_empty() {
+        own.insert("blocking".to_string(), Value::Table(own_blocking));
+    }
+
+    Ok(own)
+}
+
+/// A profile file before its parents are merged in
+struct ProfileFile {
+    path: PathBuf,
+    /// from `extends`, merged in order so later parents override earlier ones
+    parents: Vec<String>,
+    table: Table,
+}
+
+fn load_profiles(path: PathBuf, diagnostics: &mut Vec<ConfigDiagnostic>) -> Vec<Profile> {
+    let files = read_profile_files(&path, diagnostics);
+
     let mut profiles = vec![];
-    for file in fs::read_dir(path)? {
-        let file = file?;
-        let path = file.path();
+    for (name, file) in files.iter() {
+        let table = match resolve_profile(name, &files, &mut vec![]) {
+            Ok(table) => table,
+            Err(e) => {
+                // every profile in a cycle finds it
+                if !diagnostics.contains(&e) {
+                    diagnostics.push(e);
+                }
+                continue;
+            }
+        };
+        let mut profile = match Value::Table(table).try_into::<Profile>() {
+            Ok(profile) => profile,
+            Err(e) => {
+                diagnostics.push(diagnostic(&file.path, None, e));
+                continue;
+            }
+        };
+        profile.name = name.clone();
+        profile.extends = file.parents.clone();
+
+        diagnostics.extend(validate_profile(&profile).into_iter()
+            .map(|(field, message)| diagnostic(&file.path, Some(field), message)));
+        profiles.push(profile);
+    }
+
+    profiles
+}
+
+/// The profile files by name, the ones that can't be read are left out
+fn read_profile_files(path: &Path, diagnostics: &mut Vec<ConfigDiagnostic>) -> BTreeMap<String, ProfileFile> {
+    let dir = match fs::read_dir(path) {
+        Ok(dir) => dir,
+        Err(e) => {
+            diagnostics.push(diagnostic(path, None, e));
+            return BTreeMap::new();
+        }
+    };
+
+    let mut files = BTreeMap::new();
+    for file in dir {
+        let path = match file {
+            Ok(file) => file.path(),
+            Err(e) => {
+                diagnostics.push(diagnostic(path, None, e));
+                continue;
+            }
+        };
         if path.extension() != Some("toml".as_ref()) {
             continue;
         }
 
-        let contents = fs::read_to_string(path)?;
+        let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+            diagnostics.push(diagnostic(&path, None, "the file name isn't valid unicode"));
+            continue;
+        };
+        match read_profile_file(&path) {
+            Ok(file) => { files.insert(name.to_string(), file); }
+            Err(e) => diagnostics.push(diagnostic(&path, None, e)),
+        }
+    }
 
-        let mut profile = toml::from_str::<Profile>(&contents)?;
-        profile.name = file.path().file_stem()
-            .and_then(|s| s.to_str())
-            .ok_or_else(|| anyhow!("Config file has no path!"))?
-            .to_string();
-        profiles.push(profile);
+    files
+}
+
+fn read_profile_file(path: &Path) -> Result<ProfileFile> {
+    let mut table: Table = fs::read_to_string(path)?.parse()?;
+    let parents = match table.remove("extends") {
+        None => vec![],
+        Some(Value::String(parent)) => vec![parent],
+        Some(parents) => parents.try_into().map_err(|_| anyhow!("extends has to be a profile name or a list of them"))?,
+    };
+
+    Ok(ProfileFile { path: path.to_path_buf(), parents, table })
+}
+
+/// The profile's table with its parents merged in. `chain` holds the profiles that are being resolved to find cycles
+fn resolve_profile(name: &str, files: &BTreeMap<String, ProfileFile>, chain: &mut Vec<String>) -> Result<Table, ConfigDiagnostic> {
+    let file = &files[name];
+    chain.push(name.to_string());
+
+    let mut table = Table::new();
+    for parent in file.parents.iter() {
+        let extends = || Some("extends".to_string());
+        if chain.contains(parent) {
+            return Err(diagnostic(&file.path, extends(), format!("extending {parent} makes a cycle: {} -> {parent}", chain.join(" -> "))));
+        }
+        if !files.contains_key(parent) {
+            return Err(diagnostic(&file.path, extends(), format!("{parent} doesn't exist or couldn't be read")));
+        }
+        merge_profile(&mut table, resolve_profile(parent, files, chain)?);
+    }
+    merge_profile(&mut table, file.table.clone());
+
+    chain.pop();
+    Ok(table)
+}
+
+/// Values of `child` override the ones in `base`, except for the lists in `blocking` which are combined.
+/// If `child` switches `blocking.mode` its lists replace the ones of `base`, since they mean the opposite
+fn merge_profile(base: &mut Table, child: Table) {
+    for (key, value) in child {
+        match (base.remove(&key), value) {
+            (Some(Value::Table(mut blocking)), Value::Table(child)) if key == "blocking" => {
+                if child.get("mode").is_some_and(|mode| *mode != blocking_mode(&blocking)) {
+                    blocking = blocking.into_iter().filter(|(_, value)| !value.is_array()).collect();
+                }
+                for (key, value) in child {
+                    match (blocking.remove(&key), value) {
+                        (Some(Value::Array(mut list)), Value::Array(child)) => {
+                            for item in child {
+                                if !list.contains(&item) {
+                                    list.push(item);
+                                }
+                            }
+                            blocking.insert(key, Value::Array(list));
+                        }
+                        (_, value) => { blocking.insert(key, value); }
+                    }
+                }
+                base.insert(key, Value::Table(blocking));
+            }
+            (_, value) => { base.insert(key, value); }
+        }
+    }
+}
+
+/// The mode a `blocking` table is in, filling in the default
+fn blocking_mode(blocking: &Table) -> Value {
+    blocking.get("mode").cloned().unwrap_or_else(|| Value::try_from(BlockingMode::default()).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(profiles: &[(&str, &[&str], &str)]) -> BTreeMap<String, ProfileFile> {
+        profiles.iter().map(|(name, parents, toml)| (name.to_string(), ProfileFile {
+            path: PathBuf::from(format!("{name}.toml")),
+            parents: parents.iter().map(|p| p.to_string()).collect(),
+            table: toml.parse().unwrap(),
+        })).collect()
+    }
+
+    fn resolve(name: &str, files: &BTreeMap<String, ProfileFile>) -> Result<Table, ConfigDiagnostic> {
+        resolve_profile(name, files, &mut vec![])
     }
 
-    Ok(profiles)
+    #[test]
+    fn child_overrides_scalars_and_combines_lists() {
+        let files = files(&[
+            ("base", &[], "can_pause = false\n[blocking]\nwebsites = [\"a.com\", \"b.com\"]\nhide_web_video = false"),
+            ("child", &["base"], "can_pause = true\n[blocking]\nwebsites = [\"b.com\", \"c.com\"]\nhide_web_video = true"),
+        ]);
+
+        let table = resolve("child", &files).unwrap();
+        assert_eq!(table["can_pause"], Value::Boolean(true));
+        assert_eq!(table["blocking"]["hide_web_video"], Value::Boolean(true));
+        assert_eq!(table["blocking"]["websites"], Value::Array(vec!["a.com".into(), "b.com".into(), "c.com".into()]));
+    }
+
+    #[test]
+    fn switching_blocking_mode_replaces_lists() {
+        let files = files(&[
+            ("base", &[], "[blocking]\nwebsites = [\"a.com\"]\nwindow_names = [\"Steam\"]"),
+            ("child", &["base"], "[blocking]\nmode = \"Allowlist\"\nwebsites = [\"docs.rs\"]"),
+            ("grandchild", &["child"], "[blocking]\nmode = \"Allowlist\"\nwebsites = [\"github.com\"]"),
+        ]);
+
+        let table = resolve("child", &files).unwrap();
+        assert_eq!(table["blocking"]["mode"], Value::String("Allowlist".to_string()));
+        assert_eq!(table["blocking"]["websites"], Value::Array(vec!["docs.rs".into()]));
+        assert!(!table["blocking"].as_table().unwrap().contains_key("window_names"));
+
+        // the same mode still combines
+        let table = resolve("grandchild", &files).unwrap();
+        assert_eq!(table["blocking"]["websites"], Value::Array(vec!["docs.rs".into(), "github.com".into()]));
+    }
+
+    #[test]
+    fn later_parents_override_earlier_ones() {
+        let files = files(&[
+            ("root", &[], "can_pause = false\nrequires_blocker = false\n[blocking]\nwebsites = [\"root.com\"]"),
+            ("left", &["root"], "can_pause = true\n[blocking]\nwebsites = [\"left.com\"]"),
+            ("right", &["root"], "can_pause = false\nrequires_blocker = true\n[blocking]\nwebsites = [\"right.com\"]"),
+            ("diamond", &["left", "right"], ""),
+        ]);
+
+        let table = resolve("diamond", &files).unwrap();
+        assert_eq!(table["can_pause"], Value::Boolean(false));
+        assert_eq!(table["requires_blocker"], Value::Boolean(true));
+        // root is reached through both parents but only listed once
+        assert_eq!(
+            table["blocking"]["websites"],
+            Value::Array(vec!["root.com".into(), "left.com".into(), "right.com".into()]),
+        );
+    }
+
+    #[test]
+    fn cycles_are_reported() {
+        let files = files(&[
+            ("a", &["b"], ""),
+            ("b", &["c"], ""),
+            ("c", &["a"], ""),
+        ]);
+
+        let e = resolve("a", &files).unwrap_err();
+        assert_eq!(e.file, "c.toml");
+        assert_eq!(e.field.as_deref(), Some("extends"));
+        assert!(e.message.contains("a -> b -> c -> a"), "{}", e.message);
+
+        let e = resolve("b", &files).unwrap_err();
+        assert!(e.message.contains("b -> c -> a -> b"), "{}", e.message);
+    }
+
+    #[test]
+    fn profiles_extending_themselves_are_cycles() {
+        let files = files(&[("a", &["a"], "")]);
+        assert!(resolve("a", &files).unwrap_err().message.contains("cycle"));
+    }
+
+    #[test]
+    fn missing_parents_are_reported() {
+        let files = files(&[
+            ("child", &["parent"], ""),
+            ("parent", &["missing"], ""),
+        ]);
+
+        let e = resolve("child", &files).unwrap_err();
+        assert_eq!(e.file, "parent.toml");
+        assert_eq!(e.field.as_deref(), Some("extends"));
+        assert!(e.message.contains("missing doesn't exist"), "{}", e.message);
+    }
+
+    #[test]
+    fn merged_table_is_a_valid_profile() {
+        let files = files(&[
+            ("base", &[], "[pomodoro]\nwork_dur = 1500\nshort_break_dur = 300\nlong_break_dur = 900\nsmall_breaks_before_big_one = 3"),
+            ("child", &["base"], "can_pause = true"),
+        ]);
+
+        let profile = Value::Table(resolve("child", &files).unwrap()).try_into::<Profile>().unwrap();
+        assert!(profile.can_pause);
+        assert_eq!(profile.pomodoro.unwrap().small_breaks_before_big_one, 3);
+    }
 }
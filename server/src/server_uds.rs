@@ -0,0 +1,47 @@
+use crate::server_ws::{handle_connection, Broadcast};
+use crate::SState;
+use anyhow::{bail, Result};
+use futures::{SinkExt, TryStreamExt};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::broadcast::Sender;
+use tokio_util::codec::{Framed, LinesCodec};
+use tracing::{info, instrument, Instrument};
+
+/// A line is one message, this only protects against clients that never send a newline
+const MAX_LINE_LENGTH: usize = 1024 * 1024;
+
+pub fn socket_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_RUNTIME_DIR").map(|dir| PathBuf::from(dir).join("watchwah.sock"))
+}
+
+/// Serves local clients the same messages as the websocket, as newline delimited json
+#[instrument(name = "server uds", skip_all)]
+pub async fn listen(state: SState, broadcast_tx: Sender<Broadcast>) -> Result<()> {
+    let Some(path) = socket_path() else { bail!("XDG_RUNTIME_DIR isn't set") };
+
+    // a socket left behind by a previous run would make binding fail
+    if path.exists() {
+        if UnixStream::connect(&path).await.is_ok() {
+            bail!("Another server is already listening on {}", path.display())
+        }
+        fs::remove_file(&path)?;
+    }
+
+    // XDG_RUNTIME_DIR is only accessible by the user, so nobody can connect before the permissions are set
+    let listener = UnixListener::bind(&path)?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+    info!("Listening on {}", path.display());
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        info!("Connection established");
+
+        let lines = Framed::new(stream, LinesCodec::new_with_max_length(MAX_LINE_LENGTH))
+            .map_err(anyhow::Error::from);
+        let conn = SinkExt::<String>::sink_map_err(lines, anyhow::Error::from);
+        tokio::spawn(handle_connection(conn, state.clone(), broadcast_tx.subscribe()).in_current_span());
+    }
+}
@@ -1,21 +1,45 @@
-use crate::server_config::{profiles_msg};
-use crate::{timer_logic, SState};
-use anyhow::{bail, Result};
+use crate::clients::Registration;
+use crate::error::{error_reply, fail};
+use crate::server_config::profiles_msg;
+use crate::timer_logic::TimerCommand;
+use crate::{clients, history, server_config, timer_logic, SState};
+use anyhow::{anyhow, Result};
 use axum::extract::ws::{Message, WebSocket};
-use common::ws_common::{ClientToServer, ServerToClient};
+use futures::future::ready;
+use futures::{pin_mut, Sink, SinkExt, Stream, StreamExt};
+use common::stats::Stats;
+use common::ws_common::{Capability, ClientToServer, ServerToClient, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
 use std::net::SocketAddr;
 use std::ops::Deref;
+use std::time::Duration;
 use tokio::select;
 use tokio::sync::broadcast::{Receiver, Sender};
 use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::time::timeout;
 use tracing::{error, info, instrument, warn};
 use ClientToServer::*;
 
-pub fn serialize_incoming(broadcast_tx: Sender<String>) -> UnboundedSender<ServerToClient> {
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A message serialized once for all clients
+#[derive(Clone, Debug)]
+pub struct Broadcast {
+    pub msg: String,
+    /// only clients that announced this capability get the message
+    pub requires: Option<Capability>,
+}
+
+pub fn serialize_incoming(broadcast_tx: Sender<Broadcast>) -> UnboundedSender<ServerToClient> {
     let (tx, mut rx) = unbounded_channel::<ServerToClient>();
 
     tokio::spawn(async move {
         while let Some(msg) = rx.recv().await {
+            let requires = match msg {
+                ServerToClient::Event(_) => Some(Capability::Events),
+                ServerToClient::Clients(_) => Some(Capability::Clients),
+                ServerToClient::UpdateTodos(_) => Some(Capability::Todos),
+                _ => None,
+            };
             let msg = match serde_json::to_string(&msg) {
                 Ok(msg) => msg,
                 Err(e) => {
@@ -23,7 +47,7 @@ pub fn serialize_incoming(broadcast_tx: Sender<String>) -> UnboundedSender<Serve
                     continue;
                 }
             };
-            if let Err(e) = broadcast_tx.send(msg) {
+            if let Err(e) = broadcast_tx.send(Broadcast { msg, requires }) {
                 error!("Failed to send message: {e}");
             }
         }
@@ -34,37 +58,72 @@ pub fn serialize_incoming(broadcast_tx: Sender<String>) -> UnboundedSender<Serve
 
 #[instrument(name = "server ws", skip_all)]
 pub async fn handle_socket(
-    mut ws: WebSocket,
+    ws: WebSocket,
     ip: SocketAddr,
     state: SState,
-    mut rx: Receiver<String>,
+    rx: Receiver<Broadcast>,
 ) {
     info!("Connection established. Ip: {ip}");
 
-    if let Err(e) = send_welcome_message(&mut ws, &state).await {
+    // only text messages carry json, pings and the like are handled by axum
+    let conn = ws
+        .sink_map_err(anyhow::Error::from)
+        .with(|text| ready(Ok(Message::Text(text))))
+        .filter_map(|msg| ready(match msg {
+            Ok(Message::Text(text)) => Some(Ok(text)),
+            Ok(_) => None,
+            Err(e) => Some(Err(e.into())),
+        }));
+
+    handle_connection(conn, state, rx).await;
+}
+
+/// Serves a client on any transport that carries one serialized message per item
+pub async fn handle_connection<C>(conn: C, state: SState, mut rx: Receiver<Broadcast>)
+where
+    C: Stream<Item = Result<String>> + Sink<String, Error = anyhow::Error>,
+{
+    pin_mut!(conn);
+
+    let (capabilities, client) = match handshake(&mut conn, &state).await {
+        Ok(handshake) => handshake,
+        Err(e) => {
+            warn!("Handshake failed: {e}");
+            // a sink that already failed mustn't be polled again
+            if send_reply(&mut conn, &error_reply(None, &e)).await.is_ok() {
+                conn.close().await.ok();
+            }
+            return;
+        }
+    };
+
+    if let Err(e) = send_welcome_message(&mut conn, &state).await {
         error!("Failed to send welcome message: {e}");
         return;
     }
 
     loop {
         select! {
-            // message was received from websocket
-            received = ws.recv() =>
+            // message was received from the client
+            received = conn.next() =>
                 match received {
-                    Some(Ok(Message::Text(msg))) => {
-                        if let Err(e) = handle_receive(&state,msg).await {
-                            error!("Error processing message: {e}")
+                    Some(Ok(msg)) => {
+                        if let Some(reply) = handle_receive(&state, &client, msg).await {
+                            if let Err(e) = send_reply(&mut conn, &reply).await {
+                                error!("Failed to send reply: {e}");
+                            }
                         }
                     },
-                    Some(Ok(_)) => (),
-                    Some(Err(e)) => {error!("Websocket errored: {e}"); return;}
-                    None => {warn!("Websocket closed"); return;}, // stream closed
+                    Some(Err(e)) => {error!("Connection errored: {e}"); return;}
+                    None => {warn!("Connection closed"); return;}, // stream closed
                 },
             // message was received from broadcast
             rez = rx.recv() =>{
                 match rez {
-                    Ok(msg) => if let Err(e) = ws.send(Message::Text(msg)).await {
-                        error!("Failed to send message: {e}");
+                    Ok(Broadcast { msg, requires }) => if requires.is_none_or(|c| capabilities.contains(&c)) {
+                        if let Err(e) = conn.send(msg).await {
+                            error!("Failed to send message: {e}");
+                        }
                     },
                     Err(e) => error!("Broadcast error: {e}"),
                 }}
@@ -72,60 +131,164 @@ pub async fn handle_socket(
     }
 }
 
-async fn handle_receive(state: &SState, msg: String) -> Result<()> {
-    let msg = serde_json::from_str(&msg)?;
+/// Checks the client's protocol version and key before anything else is sent or processed.
+/// Returns the capabilities both sides support and the client's entry in the registry
+async fn handshake<C>(conn: &mut C, state: &SState) -> Result<(Vec<Capability>, Registration)>
+where
+    C: Stream<Item = Result<String>> + Sink<String, Error = anyhow::Error> + Unpin,
+{
+    let Hello { protocol_version, client_kind, client_version, capabilities } = recv_msg(conn).await? else {
+        fail!(InvalidMessage, "Expected a Hello message")
+    };
+    if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&protocol_version) {
+        fail!(
+            IncompatibleVersion,
+            "Protocol version {protocol_version} isn't supported, the server supports versions {MIN_PROTOCOL_VERSION} to {PROTOCOL_VERSION}"
+        )
+    }
+    info!("{client_kind:?} client using protocol version {protocol_version}");
+
+    let capabilities: Vec<_> = capabilities.into_iter()
+        .filter(|c| Capability::SUPPORTED.contains(c))
+        .collect();
+    send_reply(conn, &ServerToClient::Hello { protocol_version: PROTOCOL_VERSION, capabilities: capabilities.clone() }).await?;
 
-    if let Multiple(msgs) = msg {
-        for msg in msgs {
-            handle_msg(state, msg).await?;
+    // anyone can connect if the server doesn't have a key
+    let key = state.conf.read().await.key.clone();
+    if let Some(key) = key {
+        match recv_msg(conn).await? {
+            Authenticate { key: client_key } if keys_match(&key, &client_key) => {}
+            Authenticate { .. } => fail!(Unauthorized, "Wrong key"),
+            _ => fail!(Unauthorized, "Expected an Authenticate message"),
         }
-    } else {
-        handle_msg(state, msg).await?;
     }
 
-    return Ok(());
+    let client = clients::register(state, client_kind, client_version, protocol_version, capabilities.clone());
+    Ok((capabilities, client))
+}
 
-    async fn handle_msg(state: &SState, msg: ClientToServer) -> Result<()> {
-        let mut timer = state.timer.lock().await;
+/// Waits for the next message during the handshake
+async fn recv_msg<C>(conn: &mut C) -> Result<ClientToServer>
+where
+    C: Stream<Item = Result<String>> + Unpin,
+{
+    let msg = timeout(HANDSHAKE_TIMEOUT, conn.next())
+        .await
+        .map_err(|_| anyhow!("Client didn't finish the handshake in time"))?
+        .ok_or_else(|| anyhow!("Connection closed"))??;
 
-        let response = match msg {
-            CreateTimer {
-                goal,
-                profile_name,
-                start_in,
-            } => timer_logic::create_timer(&mut timer, state, goal, profile_name, start_in).await?,
+    Ok(serde_json::from_str(&msg)?)
+}
+
+/// Compares in constant time so the key can't be guessed from how long the check takes
+pub fn keys_match(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Returns the reply meant only for the client that sent the message
+async fn handle_receive(state: &SState, client: &Registration, msg: String) -> Option<ServerToClient> {
+    let (id, result) = match serde_json::from_str(&msg) {
+        Ok(Request { id, msg }) => (Some(id), handle_msgs(state, client, *msg).await),
+        Ok(msg) => (None, handle_msgs(state, client, msg).await),
+        Err(e) => (None, Err(e.into())),
+    };
 
-            StopTimer => timer_logic::stop_timer(&mut timer, state)?,
+    let mut replies = match result {
+        Ok(replies) => replies,
+        Err(e) => {
+            error!("Error processing message: {e}");
+            return Some(error_reply(id, &e));
+        }
+    };
+    if let Some(id) = id {
+        replies.push(ServerToClient::Ack { id });
+    }
 
-            msg @ (PauseTimer | UnpauseTimer | SkipPeriod) => {
-                let Some(ref mut timer) = *timer else {bail!("Timer is not created!") };
+    return match replies.len() {
+        0 => None,
+        1 => replies.pop(),
+        _ => Some(ServerToClient::Multiple(replies)),
+    };
 
-                match msg {
-                    PauseTimer => timer_logic::pause_timer(timer, state)?,
-                    UnpauseTimer => timer_logic::unpause_timer(timer, state)?,
-                    SkipPeriod => timer_logic::skip_period(timer, state)?,
-                    _ => unreachable!(),
-                }
+    async fn handle_msgs(state: &SState, client: &Registration, msg: ClientToServer) -> Result<Vec<ServerToClient>> {
+        let mut replies = vec![];
+        if let Multiple(msgs) = msg {
+            for msg in msgs {
+                replies.extend(handle_msg(state, client, msg).await?);
             }
+        } else {
+            replies.extend(handle_msg(state, client, msg).await?);
+        }
+        Ok(replies)
+    }
 
-            Multiple(_) => bail!("Recursive messages are not supported"),
+    async fn handle_msg(state: &SState, client: &Registration, msg: ClientToServer) -> Result<Option<ServerToClient>> {
+        if let Heartbeat = msg {
+            client.heartbeat();
+            return Ok(None);
+        }
+        // queries don't touch the timer
+        if let QueryHistory { from, to, profile } = msg {
+            let entries = tokio::task::spawn_blocking(move || history::query(from, to, profile.as_deref())).await??;
+            return Ok(Some(ServerToClient::History(entries)));
+        }
+        if let QueryStats { from, to, profile } = msg {
+            let entries = tokio::task::spawn_blocking(move || history::query(from, to, profile.as_deref())).await??;
+            return Ok(Some(ServerToClient::Stats(Box::new(Stats::compute(&entries)))));
+        }
+        if let SaveProfile { name, extends, profile } = msg {
+            tokio::task::spawn_blocking(move || server_config::save_profile(&name, &extends, &profile)).await??;
+            return Ok(None);
+        }
+        if let DeleteProfile { name } = msg {
+            tokio::task::spawn_blocking(move || server_config::delete_profile(&name)).await??;
+            return Ok(None);
+        }
+
+        let command = match msg {
+            Hello { .. } | Authenticate { .. } => fail!(InvalidMessage, "The handshake is already done"),
+            CreateTimer {
+                goal,
+                profile_name,
+                start_in,
+            } => TimerCommand::Create { goal, profile_name, start_in },
+            PauseTimer => TimerCommand::Pause,
+            UnpauseTimer => TimerCommand::Unpause,
+            StopTimer => TimerCommand::Stop,
+            SkipPeriod => TimerCommand::Skip,
+            RequestEmergencyUnlock => TimerCommand::RequestUnlock,
+            AdjustGoal { by } => TimerCommand::AdjustGoal(by),
+            AdjustPeriod { by } => TimerCommand::AdjustPeriod(by),
 
-        }.to_msg(timer.as_ref());
+            SetTodos(todos) => TimerCommand::SetTodos(todos),
+            AddTodo(text) => TimerCommand::AddTodo(text),
+            CompleteTodo { index, done } => TimerCommand::CompleteTodo { index, done },
 
-        if let Some(response) = response {
-            state.ws_tx.send(response)?;
-        }
+            Heartbeat | QueryHistory { .. } | QueryStats { .. } | SaveProfile { .. } | DeleteProfile { .. } => unreachable!(),
+            Multiple(_) => fail!(InvalidMessage, "Recursive messages are not supported"),
+            Request { .. } => fail!(InvalidMessage, "Only the outermost message can have a request id"),
+        };
+        timer_logic::send(state, command).await?;
 
-        Ok(())
+        Ok(None)
     }
 }
 
-async fn send_welcome_message(ws: &mut WebSocket, state: &SState) -> Result<()> {
+async fn send_reply<C>(conn: &mut C, msg: &ServerToClient) -> Result<()>
+where
+    C: Sink<String, Error = anyhow::Error> + Unpin,
+{
+    conn.send(serde_json::to_string(msg)?).await
+}
+
+async fn send_welcome_message<C>(conn: &mut C, state: &SState) -> Result<()>
+where
+    C: Sink<String, Error = anyhow::Error> + Unpin,
+{
     let msg = ServerToClient::Multiple(vec![
         profiles_msg(state.conf.read().await.deref()),
-        ServerToClient::UpdateTimer(state.timer.lock().await.clone().map(Box::new)),
+        ServerToClient::UpdateTimer(state.timer.borrow().clone().map(Box::new)),
     ]);
 
-    ws.send(Message::Text(serde_json::to_string(&msg)?)).await?;
-    Ok(())
-}
\ No newline at end of file
+    send_reply(conn, &msg).await
+}
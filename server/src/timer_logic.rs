@@ -1,22 +1,149 @@
-use std::cmp::Ordering;
-use crate::SState;
+use crate::error::{fail, CommandError};
+use crate::writer::{self, Write};
+use crate::{history, hooks, timer_persistence, SState};
 use anyhow::{anyhow, bail, Result};
-use chrono::{Duration, Utc};
-use common::timer::{PeriodProgress, PeriodType, Timer, TimerGoal, TimerState};
-use common::ws_common::ServerToClient;
+use chrono::{DateTime, Duration, Utc};
+use common::history::{HistoryEntry, PeriodEnd};
+use common::timer::{PeriodProgress, PeriodType, Timer, TimerGoal, TimerState, Todo};
+use common::profile::{Blocking, BlockingMode, Profile};
+use common::ws_common::{ErrorKind, ServerToClient, TimerEvent, TimerProfileChange};
+use std::sync::atomic::Ordering;
 use tokio::select;
-use tracing::{debug, error, info};
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::{oneshot, watch};
+use tokio::time::Instant;
+use tracing::{error, info, instrument, warn};
+
+/// Everything that can change the timer, except for periods running out
+#[derive(Debug)]
+pub enum TimerCommand {
+    Create {
+        goal: TimerGoal,
+        profile_name: String,
+        start_in: Option<Duration>,
+    },
+    Pause,
+    Unpause,
+    Skip,
+    Stop,
+    RequestUnlock,
+    AdjustGoal(Duration),
+    AdjustPeriod(Duration),
+    SetTodos(Vec<Todo>),
+    AddTodo(String),
+    CompleteTodo { index: usize, done: bool },
+    /// replaces the profile of the timer if it has the same name
+    RefreshProfile(Profile),
+    /// sent by [crate::clients::watchdog] when the profile requires a blocker and none is alive
+    BlockerLost,
+    BlockerReturned,
+}
+
+const MAX_POMODOROS: u32 = 1000;
+const MAX_START_IN_HOURS: i64 = 24;
+const MAX_ADJUSTMENT_HOURS: i64 = 24;
+
+pub type TimerRequest = (TimerCommand, oneshot::Sender<Result<()>>);
+
+/// Sends a command to the timer actor and waits for it to be processed
+pub async fn send(state: &SState, command: TimerCommand) -> Result<()> {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    state.timer_tx.send((command, reply_tx)).map_err(|_| anyhow!("Timer actor isn't running"))?;
+    reply_rx.await?
+}
+
+/// Owns the timer. Commands and the end of the current period are handled one at a time so they can't race each other
+#[instrument(name = "timer actor", skip_all)]
+pub async fn timer_actor(state: SState, mut rx: UnboundedReceiver<TimerRequest>, timer_tx: watch::Sender<Option<Timer>>) {
+    // continue the session from before the restart
+    let mut timer = match restore_timer() {
+        Ok(timer) => timer,
+        Err(e) => {
+            error!("Failed to restore timer: {e}");
+            None
+        }
+    };
+    writer::queue(Write::Snapshot(timer.clone().map(Box::new)));
+    timer_tx.send_replace(timer.clone());
+
+    loop {
+        let deadline = timer.as_ref().and_then(period_deadline);
+        let mut events = vec![];
+        // hooks still need the timer after a session ended
+        let previous = timer.clone();
+
+        let result = select! {
+            request = rx.recv() => {
+                let Some((command, reply)) = request else { break };
+                let result = handle_command(&mut timer, &state, command, &mut events).await;
+                let synced = match result {
+                    Ok(token) => sync(token, &state, &timer_tx, timer.as_ref(), events, previous.as_ref()),
+                    Err(e) => Err(e),
+                };
+                reply.send(synced).ok();
+                continue;
+            }
+            _ = sleep_until(deadline) => period_ran_out(&mut timer, &mut events),
+        };
+
+        if let Err(e) = result.and_then(|token| sync(token, &state, &timer_tx, timer.as_ref(), events, previous.as_ref())) {
+            error!("Failed to start next period: {e}");
+        }
+    }
+
+    info!("Stopping");
+}
 
-pub async fn create_timer(
+/// `events` collects what happened to the timer, they are sent to the clients after the timer itself
+async fn handle_command(timer: &mut Option<Timer>, state: &SState, command: TimerCommand, events: &mut Vec<TimerEvent>) -> Result<SyncToken> {
+    use TimerCommand::*;
+    match command {
+        Create { goal, profile_name, start_in } => create_timer(timer, state, goal, profile_name, start_in, events).await,
+        Stop => stop_timer(timer, events),
+        Skip => skip_period(timer, events),
+        SetTodos(todos) => set_todos(timer, todos, events),
+        CompleteTodo { index, done } => complete_todo(timer, index, done, events),
+
+        command @ (Pause | Unpause | RequestUnlock | AdjustGoal(_) | AdjustPeriod(_) | AddTodo(_) | RefreshProfile(_) | BlockerLost | BlockerReturned) => {
+            let Some(ref mut timer) = timer else { fail!(NoTimer, "Timer is not created!") };
+
+            match command {
+                Pause => pause_timer(timer, events),
+                Unpause => unpause_timer(timer, events),
+                RequestUnlock => request_unlock(timer),
+                AdjustGoal(by) => adjust_goal(timer, by),
+                AdjustPeriod(by) => adjust_period(timer, by),
+                AddTodo(text) => add_todo(timer, text),
+                RefreshProfile(profile) => refresh_profile(timer, profile),
+                BlockerLost => blocker_lost(timer, events),
+                BlockerReturned => blocker_returned(timer, events),
+                _ => unreachable!(),
+            }
+        }
+    }
+}
+
+async fn create_timer(
     timer: &mut Option<Timer>,
     state: &SState,
     goal: TimerGoal,
     profile_name: String,
     start_in: Option<Duration>,
+    events: &mut Vec<TimerEvent>,
 ) -> Result<SyncToken> {
 
     if timer.is_some() {
-        bail!("Timer is already created")
+        fail!(TimerExists, "Timer is already created")
+    }
+    if goal == TimerGoal::Pomodoros(0) {
+        fail!(InvalidArgument, "Pomodoro goal has to be at least 1")
+    }
+    // the goal is turned into a duration, larger ones would overflow it
+    if matches!(goal, TimerGoal::Pomodoros(n) if n > MAX_POMODOROS) {
+        fail!(InvalidArgument, "Pomodoro goal can't be more than {MAX_POMODOROS}")
+    }
+    if start_in.is_some_and(|start_in| start_in < Duration::zero() || start_in > Duration::hours(MAX_START_IN_HOURS)) {
+        fail!(InvalidArgument, "The session has to start in 0 to {MAX_START_IN_HOURS} hours")
     }
 
     // find profile
@@ -27,11 +154,15 @@ pub async fn create_timer(
         .profiles
         .iter()
         .find(|p| p.name == profile_name)
-        .ok_or_else(|| anyhow!("Profile not found"))?
+        .ok_or_else(|| CommandError::new(ErrorKind::UnknownProfile, format!("Profile {profile_name} not found")))?
         .clone();
 
+    if matches!(goal, TimerGoal::Pomodoros(_)) && profile.pomodoro.is_none() {
+        fail!(InvalidArgument, "Profile {profile_name} doesn't have pomodoro settings")
+    }
+
     // create new timer
-    *timer = Some(Timer {
+    let mut new_timer = Timer {
         profile,
         goal,
         state: TimerState {
@@ -39,91 +170,371 @@ pub async fn create_timer(
             period: PeriodType::Uninit,
             total_dur_worked: Duration::zero(),
             small_breaks: 0,
+            pomodoros_done: 0,
+            unlock_at: None,
+            blocker_lost: false,
         },
-    });
-
-    let timer = timer.as_mut().unwrap();
+        todos: vec![],
+    };
 
-    _ = if let Some(start_in) = start_in {
+    let period = if let Some(start_in) = start_in {
         // start with a break
-        set_next_period(timer, state.clone(), (PeriodType::Starting, Some(start_in)))?
+        (PeriodType::Starting, Some(start_in))
     } else {
         // start normally
-        set_next_period(timer, state.clone(), pick_next_period(timer))?
+        pick_next_period(&new_timer).ok_or_else(|| CommandError::new(ErrorKind::InvalidArgument, "Goal is already reached"))?
     };
+    set_next_period(&mut new_timer, period, PeriodEnd::Finished, events);
+    *timer = Some(new_timer);
+    state.metrics.sessions_started.fetch_add(1, Ordering::Relaxed);
+
     info!("Timer created");
     Ok(SyncToken::Timer)
 }
 
-pub fn pause_timer(timer: &mut Timer, state: &SState) -> Result<SyncToken> {
-    state.cancel_timer_task.notify_waiters();
+fn pause_timer(timer: &mut Timer, events: &mut Vec<TimerEvent>) -> Result<SyncToken> {
+    if !timer.profile.can_pause {
+        fail!(NotAllowed, "Profile {} doesn't allow pausing", timer.profile.name)
+    }
 
+    let now = Utc::now();
     timer.state.progress = match timer.state.progress {
-        PeriodProgress::Uninit => bail!("Timer is not initialized"),
-        PeriodProgress::Paused { .. } => bail!("Timer is already paused"),
+        PeriodProgress::Uninit => fail!(InvalidState, "Timer is not initialized"),
+        PeriodProgress::Paused { .. } => fail!(InvalidState, "Timer is already paused"),
         PeriodProgress::Running {
             elapsed,
             start,
             limit,
-        } => PeriodProgress::Paused {
-            elapsed: elapsed + (Utc::now() - start),
-            limit,
-        },
+        } => {
+            history::record(timer, PeriodEnd::Paused, now);
+            PeriodProgress::Paused {
+                elapsed: elapsed + (now - start),
+                limit,
+            }
+        }
     };
 
+    events.push(TimerEvent::Paused);
     info!("Timer paused");
     Ok(SyncToken::TimerState)
 }
 
-pub fn unpause_timer(timer: &mut Timer, state: &SState) -> Result<SyncToken> {
+fn unpause_timer(timer: &mut Timer, events: &mut Vec<TimerEvent>) -> Result<SyncToken> {
     timer.state.progress = match timer.state.progress {
-        PeriodProgress::Uninit => bail!("Timer is not initialized"),
-        PeriodProgress::Running { .. } => bail!("Timer is already running!"),
-        PeriodProgress::Paused { elapsed, limit } => {
-            if let Some(limit) = limit {
-                let awake_in = (limit - elapsed).max(Duration::zero());
-                spawn_task(state.clone(), awake_in);
-            }
-
-            PeriodProgress::Running {
-                elapsed,
-                start: Utc::now(),
-                limit,
-            }
-        }
+        PeriodProgress::Uninit => fail!(InvalidState, "Timer is not initialized"),
+        PeriodProgress::Running { .. } => fail!(InvalidState, "Timer is already running!"),
+        PeriodProgress::Paused { elapsed, limit } => PeriodProgress::Running {
+            elapsed,
+            start: Utc::now(),
+            limit,
+        },
     };
 
+    events.push(TimerEvent::Resumed);
     info!("Timer unpaused");
     Ok(SyncToken::TimerState)
 }
 
-pub fn skip_period(timer: &mut Timer, state: &SState) -> Result<SyncToken> {
-    let msg = set_next_period(timer, state.clone(), pick_next_period(timer))?;
+fn skip_period(timer: &mut Option<Timer>, events: &mut Vec<TimerEvent>) -> Result<SyncToken> {
+    let Some(ref mut current) = timer else { fail!(NoTimer, "Timer is not created!") };
+    if current.state.period == PeriodType::Work && !current.profile.can_skip_work {
+        fail!(NotAllowed, "Profile {} doesn't allow skipping work", current.profile.name)
+    }
+
+    let token = match pick_next_period(current) {
+        Some(period) => {
+            set_next_period(current, period, PeriodEnd::Skipped, events);
+            SyncToken::TimerState
+        }
+        None => {
+            end_timer(timer, PeriodEnd::Skipped, events)?;
+            SyncToken::Timer
+        }
+    };
 
     info!("Timer skipped period");
-    Ok(msg)
+    Ok(token)
 }
 
-pub fn stop_timer(timer: &mut Option<Timer>, state: &SState) -> Result<SyncToken> {
-    if timer.is_none() {
-        bail!("Timer isn't created!")
+fn stop_timer(timer: &mut Option<Timer>, events: &mut Vec<TimerEvent>) -> Result<SyncToken> {
+    let Some(ref current) = timer else { fail!(NoTimer, "Timer is not created!") };
+    if !current.can_stop() {
+        fail!(NotAllowed, "Profile {} doesn't allow stopping before the goal is fulfilled", current.profile.name)
     }
-    state.cancel_timer_task.notify_waiters();
-    *timer = None;
+    end_timer(timer, PeriodEnd::Stopped, events)?;
 
     info!("Timer stopped");
     Ok(SyncToken::Timer)
 }
 
+fn request_unlock(timer: &mut Timer) -> Result<SyncToken> {
+    let Some(delay) = timer.profile.emergency_unlock_delay else {
+        fail!(NotAllowed, "Profile {} doesn't allow emergency unlocks", timer.profile.name)
+    };
+    if timer.state.unlock_at.is_some() {
+        fail!(InvalidState, "Emergency unlock was already requested")
+    }
+    let unlock_at = Utc::now().checked_add_signed(delay)
+        .ok_or_else(|| CommandError::new(ErrorKind::InvalidState, "Emergency unlock delay is too long"))?;
+    timer.state.unlock_at = Some(unlock_at);
+
+    info!("Emergency unlock requested, unlocking in {delay}");
+    Ok(SyncToken::TimerState)
+}
+
+/// Recorded once per work period, the period goes on
+fn blocker_lost(timer: &mut Timer, events: &mut Vec<TimerEvent>) -> Result<SyncToken> {
+    if timer.state.blocker_lost {
+        return Ok(SyncToken::None);
+    }
+    timer.state.blocker_lost = true;
+    history::record(timer, PeriodEnd::BlockerLost, Utc::now());
+    events.push(TimerEvent::BlockerLost);
+
+    warn!("Profile {} requires a blocker but none is alive", timer.profile.name);
+    Ok(SyncToken::TimerState)
+}
+
+fn blocker_returned(timer: &mut Timer, events: &mut Vec<TimerEvent>) -> Result<SyncToken> {
+    if !timer.state.blocker_lost {
+        return Ok(SyncToken::None);
+    }
+    timer.state.blocker_lost = false;
+    events.push(TimerEvent::BlockerReturned);
+
+    info!("Blocker is back");
+    Ok(SyncToken::TimerState)
+}
+
+fn adjust_goal(timer: &mut Timer, by: Duration) -> Result<SyncToken> {
+    check_adjustment(by)?;
+    if by < Duration::zero() && !timer.profile.can_stop_before_goal_is_fulfilled {
+        fail!(NotAllowed, "Profile {} doesn't allow shortening the goal", timer.profile.name)
+    }
+    let TimerGoal::Time(goal) = timer.goal else {
+        fail!(InvalidState, "Only time goals can be adjusted")
+    };
+    let new_goal = goal.checked_add(&by).ok_or_else(too_long)?.max(timer.state.dur_worked());
+
+    // the current work period follows the goal, it gets cut if it would go past it or lengthened up to the work duration
+    if timer.state.period == PeriodType::Work {
+        if let Some(limit) = timer.state.progress.limit() {
+            // the time left of the new goal
+            let time_left = new_goal - timer.state.total_dur_worked;
+            let mut new_limit = limit.checked_add(&time_left).ok_or_else(too_long)?;
+            if let Some(ref pomodoro) = timer.profile.pomodoro {
+                new_limit = new_limit.min(pomodoro.work_dur);
+            }
+            let new_limit = new_limit.max(timer.state.progress.elapsed());
+            change_limit(timer, new_limit - limit)?;
+        }
+    }
+    timer.goal = TimerGoal::Time(new_goal);
+
+    info!("Goal adjusted by {by}");
+    Ok(SyncToken::Timer)
+}
+
+fn adjust_period(timer: &mut Timer, by: Duration) -> Result<SyncToken> {
+    check_adjustment(by)?;
+    if by < Duration::zero() && timer.state.period == PeriodType::Work && !timer.profile.can_skip_work {
+        fail!(NotAllowed, "Profile {} doesn't allow shortening work", timer.profile.name)
+    }
+    // extending anything but work postpones work just like a pause does
+    if by > Duration::zero() && timer.state.period != PeriodType::Work && !timer.profile.can_pause {
+        fail!(NotAllowed, "Profile {} doesn't allow extending breaks", timer.profile.name)
+    }
+    let Some(limit) = timer.state.progress.limit() else {
+        fail!(InvalidState, "Period has no time limit")
+    };
+
+    // if it gets shorter than the elapsed time the period ends right away
+    let new_limit = limit.checked_add(&by).ok_or_else(too_long)?.max(timer.state.progress.elapsed());
+    change_limit(timer, new_limit - limit)?;
+
+    info!("Period adjusted by {by}");
+    Ok(SyncToken::TimerState)
+}
+
+fn set_todos(timer: &mut Option<Timer>, todos: Vec<Todo>, events: &mut Vec<TimerEvent>) -> Result<SyncToken> {
+    let Some(ref mut current) = timer else { fail!(NoTimer, "Timer isn't created!") };
+    // the todos are the goal, only adding and completing them can't get around the lock
+    if current.goal == TimerGoal::Todos && !current.can_stop() {
+        let mut new_texts: Vec<_> = todos.iter().map(|todo| todo.text.as_str()).collect();
+        for todo in current.todos.iter().filter(|todo| !todo.done) {
+            let Some(i) = new_texts.iter().position(|text| *text == todo.text) else {
+                fail!(NotAllowed, "Profile {} doesn't allow removing todos before the goal is fulfilled", current.profile.name)
+            };
+            new_texts.swap_remove(i);
+        }
+    }
+    current.todos = todos;
+
+    info!("Todos set");
+    finish_if_todos_completed(timer, events)
+}
+
+fn add_todo(timer: &mut Timer, text: String) -> Result<SyncToken> {
+    timer.todos.push(Todo { text, done: false });
+
+    info!("Todo added");
+    Ok(SyncToken::Todos)
+}
+
+fn complete_todo(timer: &mut Option<Timer>, index: usize, done: bool, events: &mut Vec<TimerEvent>) -> Result<SyncToken> {
+    let Some(ref mut current) = timer else { fail!(NoTimer, "Timer isn't created!") };
+    let todo = current.todos.get_mut(index).ok_or_else(|| CommandError::new(ErrorKind::InvalidArgument, "Todo doesn't exist"))?;
+    todo.done = done;
+
+    info!("Todo {index} marked as {}", if done { "done" } else { "not done" });
+    finish_if_todos_completed(timer, events)
+}
+
+fn refresh_profile(timer: &mut Timer, profile: Profile) -> Result<SyncToken> {
+    // the timer might have been replaced since the config was reloaded
+    if timer.profile.name != profile.name {
+        return Ok(SyncToken::None);
+    }
+    timer.profile = profile;
+
+    info!("Timer profile refreshed");
+    Ok(SyncToken::Timer)
+}
+
+/// What should happen to the timer's profile after a config reload.
+/// The change is deferred to the end of the session if the profile was removed, it would break the goal
+/// or it lifts restrictions, otherwise editing the profile would get around them
+pub fn profile_change(timer: &Timer, profiles: &[Profile]) -> Option<TimerProfileChange> {
+    match profiles.iter().find(|p| p.name == timer.profile.name) {
+        Some(profile) if *profile == timer.profile => None,
+        Some(profile) if matches!(timer.goal, TimerGoal::Pomodoros(_)) && profile.pomodoro.is_none() => Some(TimerProfileChange::Deferred),
+        Some(profile) if loosens_restrictions(&timer.profile, profile) => Some(TimerProfileChange::Deferred),
+        Some(_) => Some(TimerProfileChange::Applied),
+        None => Some(TimerProfileChange::Deferred),
+    }
+}
+
+fn loosens_restrictions(old: &Profile, new: &Profile) -> bool {
+    let unlock_sooner = match (old.emergency_unlock_delay, new.emergency_unlock_delay) {
+        (None, Some(_)) => true,
+        (Some(old), Some(new)) => new < old,
+        (_, None) => false,
+    };
+
+    (new.can_pause && !old.can_pause)
+        || (new.can_skip_work && !old.can_skip_work)
+        || (new.can_stop_before_goal_is_fulfilled && !old.can_stop_before_goal_is_fulfilled)
+        || (old.requires_blocker && !new.requires_blocker)
+        || unlock_sooner
+        || loosens_blocking(&old.blocking, &new.blocking)
+}
+
+/// Changing the mode turns the lists inside out, so it counts as loosening either way
+fn loosens_blocking(old: &Blocking, new: &Blocking) -> bool {
+    // in denylist mode removing an item unblocks it, in allowlist mode adding one does
+    let unblocks = |old_list: &[String], new_list: &[String]| match old.mode {
+        BlockingMode::Denylist => old_list.iter().any(|item| !new_list.contains(item)),
+        BlockingMode::Allowlist => new_list.iter().any(|item| !old_list.contains(item)),
+    };
+
+    old.mode != new.mode
+        || (old.hide_web_video && !new.hide_web_video)
+        || unblocks(&old.window_names, &new.window_names)
+        || unblocks(&old.process_path, &new.process_path)
+        || unblocks(&old.websites, &new.websites)
+}
+
+/// Loads the timer saved by the previous run and catches up on the periods that ended while the server was down
+fn restore_timer() -> Result<Option<Timer>> {
+    let Some(restored) = timer_persistence::load()? else { return Ok(None) };
+
+    let (restored, entries) = catch_up(restored, Utc::now());
+    entries.into_iter().for_each(|entry| writer::queue(Write::History(entry)));
+
+    match restored {
+        Some(_) => info!("Timer restored"),
+        None => info!("Restored timer already finished"),
+    }
+    Ok(restored)
+}
+
+/// Runs the periods that ended before `now`. Returns the timer, None if its goal was reached,
+/// and the history entries of the periods that ended
+fn catch_up(mut timer: Timer, now: DateTime<Utc>) -> (Option<Timer>, Vec<HistoryEntry>) {
+    let mut entries = vec![];
+    while let Some(period_end) = period_end(&timer) {
+        if period_end > now {
+            break;
+        }
+
+        let Some(period) = finish_period(&mut timer) else {
+            entries.extend(history::entry(&timer, PeriodEnd::GoalReached, period_end));
+            return (None, entries);
+        };
+        entries.extend(history::entry(&timer, PeriodEnd::Finished, period_end));
+        // the next period started when the previous one ended, not now
+        start_period(&mut timer, period, period_end);
+    }
+
+    (Some(timer), entries)
+}
+
 // region Helpers
 
+/// Starts the next period or ends the timer if the goal was reached
+fn period_ran_out(timer: &mut Option<Timer>, events: &mut Vec<TimerEvent>) -> Result<SyncToken> {
+    let Some(ref mut current) = timer else { bail!("Timer isn't created!") };
+
+    match finish_period(current) {
+        Some(period) => {
+            set_next_period(current, period, PeriodEnd::Finished, events);
+            Ok(SyncToken::TimerState)
+        }
+        None => {
+            end_timer(timer, PeriodEnd::GoalReached, events)?;
+            info!("Goal reached, timer finished");
+            Ok(SyncToken::Timer)
+        }
+    }
+}
+
+/// Removes the timer, `ended` is how its current period ended
+fn end_timer(timer: &mut Option<Timer>, ended: PeriodEnd, events: &mut Vec<TimerEvent>) -> Result<()> {
+    let Some(ref current) = timer else {
+        bail!("Timer isn't created!")
+    };
+    history::record(current, ended, Utc::now());
+
+    events.push(TimerEvent::PeriodEnded { period: current.state.period, reason: ended });
+    if ended == PeriodEnd::GoalReached {
+        events.push(TimerEvent::GoalReached);
+    }
+    let finished = ended == PeriodEnd::GoalReached || TimerGoal::is_fulfilled(current);
+    events.push(TimerEvent::SessionStopped { finished });
+
+    *timer = None;
+    Ok(())
+}
+
+/// Ends the session if the goal is to finish the todos and all of them are done
+fn finish_if_todos_completed(timer: &mut Option<Timer>, events: &mut Vec<TimerEvent>) -> Result<SyncToken> {
+    match timer {
+        Some(current) if current.goal == TimerGoal::Todos && current.todos_completed() => {
+            end_timer(timer, PeriodEnd::GoalReached, events)?;
+            info!("All todos are done, timer finished");
+            Ok(SyncToken::Timer)
+        }
+        _ => Ok(SyncToken::Todos),
+    }
+}
+
 /// Used to determine what ServerToClient message to send to clients to share the timer state
 #[must_use]
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum SyncToken {
-    #[allow(dead_code)]
     None,
     TimerState,
+    Todos,
     Timer
 }
 
@@ -132,21 +543,59 @@ impl SyncToken {
         Some(match self {
             SyncToken::None => return None,
             SyncToken::TimerState => {
-                let Some(timer) = timer else {return None};
+                let timer = timer?;
                 ServerToClient::UpdateTimerState(Box::new(timer.state.clone()))
             },
+            SyncToken::Todos => ServerToClient::UpdateTodos(timer?.todos.clone()),
             SyncToken::Timer => ServerToClient::UpdateTimer(timer.map(|t| Box::new(t.clone()))),
         })
     }
 }
 
-fn pick_next_period(timer: &Timer) -> (PeriodType, Option<Duration>) {
+/// Publishes the timer, saves the snapshot, runs the hooks and sends the changes and then the events to the clients
+fn sync(
+    token: SyncToken,
+    state: &SState,
+    timer_tx: &watch::Sender<Option<Timer>>,
+    timer: Option<&Timer>,
+    events: Vec<TimerEvent>,
+    previous: Option<&Timer>,
+) -> Result<()> {
+    if token == SyncToken::None {
+        return Ok(());
+    }
+
+    timer_tx.send_replace(timer.cloned());
+    writer::queue(Write::Snapshot(timer.map(|t| Box::new(t.clone()))));
+    hooks::run(state, &events, timer.or(previous));
+    state.metrics.record_events(&events);
+
+    if let Some(msg) = token.to_msg(timer) {
+        state.ws_tx.send(msg)?;
+    }
+    for event in events {
+        state.ws_tx.send(ServerToClient::Event(event))?;
+    }
+    Ok(())
+}
+
+/// Counts the period that ran out and picks the next one
+fn finish_period(timer: &mut Timer) -> Option<(PeriodType, Option<Duration>)> {
+    if timer.state.period == PeriodType::Work {
+        timer.state.pomodoros_done += 1;
+    }
+    pick_next_period(timer)
+}
+
+/// Returns None if the goal is reached and the timer should stop
+fn pick_next_period(timer: &Timer) -> Option<(PeriodType, Option<Duration>)> {
     use PeriodType::*;
+    if TimerGoal::is_reached(timer) {
+        return None;
+    }
+
     let time_left = TimerGoal::time_left(timer);
-    if time_left == Some(Duration::zero()){
-        // we stop the timer by sending in a zero duration period
-        (Uninit, Some(Duration::zero()))
-    } else if let Some(pomodoro) = &timer.profile.pomodoro {
+    Some(if let Some(pomodoro) = &timer.profile.pomodoro {
         // pomodoro logic
         match timer.state.period {
             Work => {
@@ -164,16 +613,30 @@ fn pick_next_period(timer: &Timer) -> (PeriodType, Option<Duration>) {
     } else {
         // normal logic
         (Work, time_left )
-    }
+    })
 }
 
-fn set_next_period(timer: &mut Timer, state: SState, period: (PeriodType, Option<Duration>)) -> Result<SyncToken> {
+/// Starts the given period, `ended` is how the current one ended
+fn set_next_period(timer: &mut Timer, period: (PeriodType, Option<Duration>), ended: PeriodEnd, events: &mut Vec<TimerEvent>) {
+    let now = Utc::now();
+    history::record(timer, ended, now);
+    if timer.state.period != PeriodType::Uninit {
+        events.push(TimerEvent::PeriodEnded { period: timer.state.period, reason: ended });
+    }
+
+    start_period(timer, period, now);
+    events.push(TimerEvent::PeriodStarted { period: period.0, limit: period.1 });
 
-    // setup next period
+    info!("Next period: {period:?}");
+}
+
+/// Sets up the period without recording anything
+fn start_period(timer: &mut Timer, period: (PeriodType, Option<Duration>), start: DateTime<Utc>) {
     timer.state.period = period.0;
+    timer.state.blocker_lost = false;
     timer.state.progress = PeriodProgress::Running {
         elapsed: Duration::zero(),
-        start: Utc::now(),
+        start,
         limit: period.1,
     };
 
@@ -187,57 +650,204 @@ fn set_next_period(timer: &mut Timer, state: SState, period: (PeriodType, Option
         PeriodType::LongBreak => timer.state.small_breaks = 0,
         _ => {}
     };
+}
 
-    // make sure there are no other tasks
-    state.cancel_timer_task.notify_waiters();
+/// Changes the limit of the current period, the actor picks up the new deadline on its own
+fn change_limit(timer: &mut Timer, by: Duration) -> Result<()> {
+    // the planned work period is already counted as worked
+    let total_dur_worked = match timer.state.period {
+        PeriodType::Work => timer.state.total_dur_worked.checked_add(&by).ok_or_else(too_long)?,
+        _ => timer.state.total_dur_worked,
+    };
 
-    // spawn timer task
-    if let Some(dur) = period.1 {
-        spawn_task(state, dur);
+    match timer.state.progress {
+        PeriodProgress::Running { ref mut limit, .. } | PeriodProgress::Paused { ref mut limit, .. } => {
+            if let Some(limit) = limit {
+                *limit = limit.checked_add(&by).ok_or_else(too_long)?;
+            }
+        }
+        PeriodProgress::Uninit => return Ok(()),
     }
+    timer.state.total_dur_worked = total_dur_worked;
+    Ok(())
+}
 
-    info!("Next period: {period:?}");
-    Ok(SyncToken::TimerState)
+fn check_adjustment(by: Duration) -> Result<()> {
+    if by > Duration::hours(MAX_ADJUSTMENT_HOURS) || by < -Duration::hours(MAX_ADJUSTMENT_HOURS) {
+        fail!(InvalidArgument, "Adjustments can't be more than {MAX_ADJUSTMENT_HOURS} hours")
+    }
+    Ok(())
 }
 
-fn spawn_task(state: SState, awake_in: Duration) {
-    // cancel any existing timer tasks
-    state.cancel_timer_task.notify_waiters();
+fn too_long() -> CommandError {
+    CommandError::new(ErrorKind::InvalidArgument, "The timer can't be that long")
+}
 
-    // spawn task
-    tokio::spawn(async move {
-        if let Err(e) = timer_task(state, awake_in).await {
-            error!("Error in timer task: {e}")
-        }
-    });
-
-    async fn timer_task(state: SState, awake_in: Duration) -> Result<()> {
-        if awake_in <= Duration::zero() {
-            // stop the timer
-            // todo: hacky solution, far from ideal
-            if let Some(msg) = stop_timer(&mut *state.timer.lock().await, &state)?.to_msg(None) {
-                state.ws_tx.send(msg).unwrap();
-            }
-            return Ok(());
-        }
+/// When the current period runs out, None if it's paused, has no limit or ends too far in the future to represent
+fn period_end(timer: &Timer) -> Option<DateTime<Utc>> {
+    match timer.state.progress {
+        PeriodProgress::Running { elapsed, start, limit: Some(limit) } => start.checked_add_signed(limit.checked_sub(&elapsed)?),
+        _ => None,
+    }
+}
 
-        // await for the task to cet cancelled or the duration to pass
-        select! {
-                _ = state.cancel_timer_task.notified() => return Ok(()),
-                _ = tokio::time::sleep(awake_in.to_std()?) => { }
-        }
+fn period_deadline(timer: &Timer) -> Option<Instant> {
+    let left = period_end(timer)? - Utc::now();
+    Instant::now().checked_add(left.to_std().unwrap_or_default())
+}
 
-        // start next period
-        let mut timer = state.timer.lock().await;
-        let timer = timer.as_mut().ok_or_else(|| anyhow!("Timer isn't created!"))?;
-        let msg = set_next_period(timer, state.clone(), pick_next_period(timer))?.to_msg(Some(timer));
+/// Waits until the deadline, forever if there is none
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+// endregion
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::profile::PomodoroSettings;
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2023-04-01T10:00:00Z").unwrap().with_timezone(&Utc) + Duration::minutes(minutes)
+    }
 
-        // sync clients if necessary
-        if let Some(msg) = msg {
-            state.ws_tx.send(msg)?;
+    /// A pomodoro timer whose first work period started at [at(0)]
+    fn pomodoro_timer(goal: u32) -> Timer {
+        let profile = Profile {
+            name: "test".to_string(),
+            pomodoro: Some(PomodoroSettings {
+                work_dur: Duration::minutes(25),
+                short_break_dur: Duration::minutes(5),
+                long_break_dur: Duration::minutes(15),
+                small_breaks_before_big_one: 1,
+            }),
+            ..Default::default()
+        };
+        let mut timer = Timer {
+            profile,
+            goal: TimerGoal::Pomodoros(goal),
+            state: TimerState {
+                progress: PeriodProgress::Uninit,
+                period: PeriodType::Uninit,
+                total_dur_worked: Duration::zero(),
+                small_breaks: 0,
+                pomodoros_done: 0,
+                unlock_at: None,
+                blocker_lost: false,
+            },
+            todos: vec![],
+        };
+        start_period(&mut timer, (PeriodType::Work, Some(Duration::minutes(25))), at(0));
+        timer
+    }
+
+    #[test]
+    fn timer_that_finished_while_down_is_dropped() {
+        let (timer, entries) = catch_up(pomodoro_timer(1), at(30));
+
+        assert!(timer.is_none());
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].period, PeriodType::Work);
+        assert_eq!(entries[0].ended, PeriodEnd::GoalReached);
+        assert_eq!(entries[0].time, at(25));
+        assert_eq!(entries[0].actual, Duration::minutes(25));
+    }
+
+    #[test]
+    fn paused_timer_is_kept() {
+        let mut timer = pomodoro_timer(1);
+        timer.state.progress = PeriodProgress::Paused { elapsed: Duration::minutes(10), limit: Some(Duration::minutes(25)) };
+
+        let (restored, entries) = catch_up(timer, at(600));
+
+        let restored = restored.unwrap();
+        assert!(entries.is_empty());
+        assert_eq!(restored.state.period, PeriodType::Work);
+        assert_eq!(restored.state.progress.elapsed(), Duration::minutes(10));
+        assert_eq!(restored.state.pomodoros_done, 0);
+    }
+
+    #[test]
+    fn running_period_that_didnt_end_is_kept() {
+        let (restored, entries) = catch_up(pomodoro_timer(1), at(20));
+
+        assert!(entries.is_empty());
+        assert_eq!(restored.unwrap().state.progress.elapsed_at(at(20)), Duration::minutes(20));
+    }
+
+    #[test]
+    fn catches_up_across_several_periods() {
+        // work 0-25, short break 25-30, work 30-55, long break 55-70, work 70-95
+        let (restored, entries) = catch_up(pomodoro_timer(3), at(80));
+
+        let restored = restored.unwrap();
+        let periods: Vec<_> = entries.iter().map(|e| (e.period, e.time, e.ended)).collect();
+        assert_eq!(periods, vec![
+            (PeriodType::Work, at(25), PeriodEnd::Finished),
+            (PeriodType::ShortBreak, at(30), PeriodEnd::Finished),
+            (PeriodType::Work, at(55), PeriodEnd::Finished),
+            (PeriodType::LongBreak, at(70), PeriodEnd::Finished),
+        ]);
+        assert_eq!(restored.state.period, PeriodType::Work);
+        assert_eq!(restored.state.pomodoros_done, 2);
+        assert_eq!(restored.state.small_breaks, 0);
+        // the current period started when the last one ended, not when the server came back
+        assert_eq!(restored.state.progress.elapsed_at(at(80)), Duration::minutes(10));
+        assert_eq!(period_end(&restored), Some(at(95)));
+    }
+
+    #[test]
+    fn periods_ending_too_far_in_the_future_have_no_end() {
+        let mut timer = pomodoro_timer(1);
+        start_period(&mut timer, (PeriodType::Work, Some(Duration::max_value())), at(0));
+
+        assert_eq!(period_end(&timer), None);
+        assert_eq!(period_deadline(&timer), None);
+    }
+
+    #[test]
+    fn goal_reached_after_several_periods() {
+        let (restored, entries) = catch_up(pomodoro_timer(2), at(600));
+
+        assert!(restored.is_none());
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[2].period, PeriodType::Work);
+        assert_eq!(entries[2].time, at(55));
+        assert_eq!(entries[2].ended, PeriodEnd::GoalReached);
+    }
+
+    fn blocking(mode: BlockingMode, websites: &[&str]) -> Profile {
+        Profile {
+            blocking: Blocking {
+                websites: websites.iter().map(|site| site.to_string()).collect(),
+                mode,
+                ..Default::default()
+            },
+            ..Default::default()
         }
+    }
+
+    #[test]
+    fn shrinking_a_denylist_loosens_restrictions() {
+        let old = blocking(BlockingMode::Denylist, &["a.com", "b.com"]);
+        assert!(loosens_restrictions(&old, &blocking(BlockingMode::Denylist, &["a.com"])));
+        assert!(!loosens_restrictions(&old, &blocking(BlockingMode::Denylist, &["b.com", "a.com", "c.com"])));
+    }
+
+    #[test]
+    fn growing_an_allowlist_loosens_restrictions() {
+        let old = blocking(BlockingMode::Allowlist, &["a.com", "b.com"]);
+        assert!(loosens_restrictions(&old, &blocking(BlockingMode::Allowlist, &["a.com", "b.com", "c.com"])));
+        assert!(!loosens_restrictions(&old, &blocking(BlockingMode::Allowlist, &["a.com"])));
+    }
 
-        Ok(())
+    #[test]
+    fn changing_the_blocking_mode_loosens_restrictions() {
+        let lists = ["a.com"];
+        assert!(loosens_restrictions(&blocking(BlockingMode::Denylist, &lists), &blocking(BlockingMode::Allowlist, &lists)));
+        assert!(loosens_restrictions(&blocking(BlockingMode::Allowlist, &lists), &blocking(BlockingMode::Denylist, &lists)));
     }
 }
-// endregion
\ No newline at end of file
@@ -0,0 +1,48 @@
+use anyhow::Result;
+use common::get_state_path;
+use common::timer::Timer;
+use std::fs;
+use std::path::PathBuf;
+use tracing::error;
+
+fn snapshot_path() -> PathBuf {
+    get_state_path().join("timer.json")
+}
+
+/// Writes the timer to the state file, or removes the file if there is no timer
+pub fn save(timer: Option<&Timer>) -> Result<()> {
+    let path = snapshot_path();
+
+    let Some(timer) = timer else {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        return Ok(());
+    };
+
+    fs::create_dir_all(get_state_path())?;
+
+    // write to a temporary file first so a crash can't leave a half written snapshot behind
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, serde_json::to_string(timer)?)?;
+    fs::rename(tmp_path, path)?;
+
+    Ok(())
+}
+
+pub fn save_logged(timer: Option<&Timer>) {
+    if let Err(e) = save(timer) {
+        error!("Failed to save timer snapshot: {e}");
+    }
+}
+
+/// Reads the timer saved by the previous run, if any
+pub fn load() -> Result<Option<Timer>> {
+    let path = snapshot_path();
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let contents = fs::read_to_string(path)?;
+    Ok(Some(serde_json::from_str(&contents)?))
+}
@@ -0,0 +1,39 @@
+use crate::{history, timer_persistence};
+use common::history::HistoryEntry;
+use common::timer::Timer;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::OnceLock;
+use std::thread;
+use tracing::error;
+
+/// A change to the files in the state directory
+pub enum Write {
+    History(HistoryEntry),
+    Snapshot(Option<Box<Timer>>),
+}
+
+static WRITER: OnceLock<Sender<Write>> = OnceLock::new();
+
+/// Hands the write to a thread of its own so the timer actor never waits for the disk.
+/// Writes happen in the order they were queued
+pub fn queue(write: Write) {
+    let writer = WRITER.get_or_init(|| {
+        let (tx, rx) = channel();
+        thread::Builder::new()
+            .name("writer".to_string())
+            .spawn(move || rx.into_iter().for_each(perform))
+            .expect("Failed to start the writer thread");
+        tx
+    });
+
+    if writer.send(write).is_err() {
+        error!("Writer thread isn't running");
+    }
+}
+
+fn perform(write: Write) {
+    match write {
+        Write::History(entry) => history::write(&entry),
+        Write::Snapshot(timer) => timer_persistence::save_logged(timer.as_deref()),
+    }
+}